@@ -0,0 +1,100 @@
+use bencher::{benchmark_group, benchmark_main, black_box, Bencher};
+use heapless::spsc::Queue;
+use morse_utils::*;
+
+const TEXT: &str = "the quick brown fox jumps over the lazy dog 0123456789 paris ";
+
+fn helper_morses(key: &MorseKey) -> heapless::Vec<Morse, 1024> {
+    MorseEncoder::new(TEXT, key).map(|m| m.unwrap()).collect()
+}
+
+// One letter or word space the way private_consume_morses_produce_letter
+// did it before LetterAssembler: the letter so far is held in a queue that is
+// taken by value and handed back, and collected into a Vec to be checked and
+// serialized
+fn queue_letter(
+    incoming: &mut core::slice::Iter<Morse>,
+    mut hold_word: Queue<Morse, 128>,
+    key: &MorseKey,
+) -> (Option<char>, Queue<Morse, 128>) {
+    if hold_word.peek() == Some(&Morse::WordSpace) {
+        hold_word.dequeue();
+        return (Some(' '), hold_word);
+    }
+    let mut next_queue = None;
+    while next_queue.is_none() {
+        match incoming.next() {
+            Some(Morse::LetterSpace) => next_queue = Some(Queue::new()),
+            Some(Morse::WordSpace) => {
+                let mut q = Queue::new();
+                q.enqueue(Morse::WordSpace).unwrap();
+                next_queue = Some(q);
+            }
+            Some(m) => hold_word.enqueue(*m).unwrap(),
+            None => break,
+        }
+    }
+    match next_queue {
+        Some(next_queue) if hold_word.is_empty() => (None, next_queue),
+        Some(next_queue) => {
+            let v: heapless::Vec<Morse, 16> = hold_word.iter().copied().collect();
+            let v = validate_morse_letter_tiny_spaces(v).unwrap();
+            let ser = serialize_morse(&v[..]).unwrap();
+            (Some(*key.get(&ser).unwrap()), next_queue)
+        }
+        None => (None, hold_word),
+    }
+}
+
+// And the way definitive_consume_morses_produce_letter kept at it until it
+// had a char or ran out of Morse
+fn queue_char(
+    incoming: &mut core::slice::Iter<Morse>,
+    mut hold_word: Queue<Morse, 128>,
+    key: &MorseKey,
+) -> (Option<char>, Queue<Morse, 128>) {
+    loop {
+        match queue_letter(incoming, hold_word, key) {
+            (None, q) if incoming.len() > 0 => hold_word = q,
+            r => break r,
+        }
+    }
+}
+
+// consume_morses as it was, cloning the held queue on every pass of its loop
+fn queue_letters(b: &mut Bencher) {
+    let key = construct_key();
+    let morses = helper_morses(key);
+    b.iter(|| {
+        let mut incoming = morses.iter();
+        let mut hold_word: Queue<Morse, 128> = Queue::new();
+        let mut chars = 0;
+        loop {
+            let (c, next) = queue_char(&mut incoming, hold_word.clone(), key);
+            hold_word = next;
+            match c {
+                Some(c) => chars += c as u32,
+                None => break,
+            }
+        }
+        black_box(chars)
+    });
+}
+
+fn assembler_letters(b: &mut Bencher) {
+    let key = construct_key();
+    let morses = helper_morses(key);
+    b.iter(|| {
+        let mut letter = LetterAssembler::new();
+        let mut chars = 0;
+        for m in morses.iter() {
+            if let Some(c) = letter.push(*m, key).unwrap() {
+                chars += c as u32;
+            }
+        }
+        black_box(chars)
+    });
+}
+
+benchmark_group!(benches, queue_letters, assembler_letters);
+benchmark_main!(benches);
@@ -0,0 +1,273 @@
+use crate::*;
+
+// American (railroad) Morse isn't here. Its C, O, R, Y, Z and & have spaces
+// inside the letter, and its L and 0 have longer dashes, none of which a
+// MorseSequenceSerialization or the 1, 3 and 7 unit classes can express.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum MorseAlphabet {
+    Latin,
+    Cyrillic,
+    Greek,
+    // Japanese kana. Shares the ITU digits but not the punctuation, whose
+    // codes are reused for kana.
+    Wabun,
+}
+
+const LATIN_LETTERS: [(MorseSequenceSerialization, char); 26] = [
+    ((2u8, 0b00000010u16), 'a'),
+    ((4u8, 0b00000001u16), 'b'),
+    ((4u8, 0b00000101u16), 'c'),
+    ((3u8, 0b00000001u16), 'd'),
+    ((1u8, 0b00000000u16), 'e'),
+    ((4u8, 0b00000100u16), 'f'),
+    ((3u8, 0b00000011u16), 'g'),
+    ((4u8, 0b00000000u16), 'h'),
+    ((2u8, 0b00000000u16), 'i'),
+    ((4u8, 0b00001110u16), 'j'),
+    ((3u8, 0b00000101u16), 'k'),
+    ((4u8, 0b00000010u16), 'l'),
+    ((2u8, 0b00000011u16), 'm'),
+    ((2u8, 0b00000001u16), 'n'),
+    ((3u8, 0b00000111u16), 'o'),
+    ((4u8, 0b00000110u16), 'p'),
+    ((4u8, 0b00001011u16), 'q'),
+    ((3u8, 0b00000010u16), 'r'),
+    ((3u8, 0b00000000u16), 's'),
+    ((1u8, 0b00000001u16), 't'),
+    ((3u8, 0b00000100u16), 'u'),
+    ((4u8, 0b00001000u16), 'v'),
+    ((3u8, 0b00000110u16), 'w'),
+    ((4u8, 0b00001001u16), 'x'),
+    ((4u8, 0b00001101u16), 'y'),
+    ((4u8, 0b00000011u16), 'z'),
+];
+
+const ITU_DIGITS: [(MorseSequenceSerialization, char); 10] = [
+    ((5u8, 0b00011110u16), '1'),
+    ((5u8, 0b00011100u16), '2'),
+    ((5u8, 0b00011000u16), '3'),
+    ((5u8, 0b00010000u16), '4'),
+    ((5u8, 0b00000000u16), '5'),
+    ((5u8, 0b00000001u16), '6'),
+    ((5u8, 0b00000011u16), '7'),
+    ((5u8, 0b00000111u16), '8'),
+    ((5u8, 0b00001111u16), '9'),
+    ((5u8, 0b00011111u16), '0'),
+];
+
+const ITU_PUNCTUATION: [(MorseSequenceSerialization, char); 19] = [
+    ((6u8, 0b00101010u16), '.'),
+    ((6u8, 0b00110011u16), ','),
+    ((6u8, 0b00000111u16), ':'),
+    ((6u8, 0b00001100u16), '?'),
+    ((6u8, 0b00011110u16), '\''),
+    ((6u8, 0b00100001u16), '-'),
+    ((5u8, 0b00001001u16), '/'),
+    ((5u8, 0b00001101u16), '('),
+    ((6u8, 0b00101101u16), ')'),
+    ((6u8, 0b00010010u16), '"'),
+    ((5u8, 0b00010001u16), '='),
+    ((5u8, 0b00001010u16), '+'),
+    ((6u8, 0b00010110u16), '@'),
+    ((5u8, 0b00000010u16), PROSIGN_AS),
+    ((5u8, 0b00010101u16), PROSIGN_KA),
+    ((6u8, 0b00101000u16), PROSIGN_SK),
+    ((5u8, 0b00001000u16), PROSIGN_SN),
+    ((8u8, 0b00000000u16), PROSIGN_HH),
+    ((9u8, 0b000111000u16), PROSIGN_SOS),
+];
+
+const CYRILLIC_LETTERS: [(MorseSequenceSerialization, char); 32] = [
+    ((2u8, 0b00000010u16), 'а'),
+    ((4u8, 0b00000001u16), 'б'),
+    ((3u8, 0b00000110u16), 'в'),
+    ((3u8, 0b00000011u16), 'г'),
+    ((3u8, 0b00000001u16), 'д'),
+    ((1u8, 0b00000000u16), 'е'),
+    ((4u8, 0b00001000u16), 'ж'),
+    ((4u8, 0b00000011u16), 'з'),
+    ((2u8, 0b00000000u16), 'и'),
+    ((4u8, 0b00001110u16), 'й'),
+    ((3u8, 0b00000101u16), 'к'),
+    ((4u8, 0b00000010u16), 'л'),
+    ((2u8, 0b00000011u16), 'м'),
+    ((2u8, 0b00000001u16), 'н'),
+    ((3u8, 0b00000111u16), 'о'),
+    ((4u8, 0b00000110u16), 'п'),
+    ((3u8, 0b00000010u16), 'р'),
+    ((3u8, 0b00000000u16), 'с'),
+    ((1u8, 0b00000001u16), 'т'),
+    ((3u8, 0b00000100u16), 'у'),
+    ((4u8, 0b00000100u16), 'ф'),
+    ((4u8, 0b00000000u16), 'х'),
+    ((4u8, 0b00000101u16), 'ц'),
+    ((4u8, 0b00000111u16), 'ч'),
+    ((4u8, 0b00001111u16), 'ш'),
+    ((4u8, 0b00001011u16), 'щ'),
+    ((5u8, 0b00011011u16), 'ъ'),
+    ((4u8, 0b00001101u16), 'ы'),
+    ((4u8, 0b00001001u16), 'ь'),
+    ((5u8, 0b00000100u16), 'э'),
+    ((4u8, 0b00001100u16), 'ю'),
+    ((4u8, 0b00001010u16), 'я'),
+];
+
+const GREEK_LETTERS: [(MorseSequenceSerialization, char); 24] = [
+    ((2u8, 0b00000010u16), 'α'),
+    ((4u8, 0b00000001u16), 'β'),
+    ((3u8, 0b00000011u16), 'γ'),
+    ((3u8, 0b00000001u16), 'δ'),
+    ((1u8, 0b00000000u16), 'ε'),
+    ((4u8, 0b00000011u16), 'ζ'),
+    ((4u8, 0b00000000u16), 'η'),
+    ((4u8, 0b00000101u16), 'θ'),
+    ((2u8, 0b00000000u16), 'ι'),
+    ((3u8, 0b00000101u16), 'κ'),
+    ((4u8, 0b00000010u16), 'λ'),
+    ((2u8, 0b00000011u16), 'μ'),
+    ((2u8, 0b00000001u16), 'ν'),
+    ((4u8, 0b00001001u16), 'ξ'),
+    ((3u8, 0b00000111u16), 'ο'),
+    ((4u8, 0b00000110u16), 'π'),
+    ((3u8, 0b00000010u16), 'ρ'),
+    ((3u8, 0b00000000u16), 'σ'),
+    ((1u8, 0b00000001u16), 'τ'),
+    ((4u8, 0b00001101u16), 'υ'),
+    ((4u8, 0b00000100u16), 'φ'),
+    ((4u8, 0b00001111u16), 'χ'),
+    ((4u8, 0b00001011u16), 'ψ'),
+    ((3u8, 0b00000110u16), 'ω'),
+];
+
+const WABUN_KANA: [(MorseSequenceSerialization, char); 55] = [
+    ((5u8, 0b00011011u16), 'ア'),
+    ((4u8, 0b00000010u16), 'カ'),
+    ((5u8, 0b00010101u16), 'サ'),
+    ((2u8, 0b00000001u16), 'タ'),
+    ((3u8, 0b00000010u16), 'ナ'),
+    ((4u8, 0b00000001u16), 'ハ'),
+    ((4u8, 0b00001001u16), 'マ'),
+    ((3u8, 0b00000110u16), 'ヤ'),
+    ((3u8, 0b00000000u16), 'ラ'),
+    ((3u8, 0b00000101u16), 'ワ'),
+    ((2u8, 0b00000010u16), 'イ'),
+    ((5u8, 0b00000101u16), 'キ'),
+    ((5u8, 0b00001011u16), 'シ'),
+    ((4u8, 0b00000100u16), 'チ'),
+    ((4u8, 0b00000101u16), 'ニ'),
+    ((5u8, 0b00010011u16), 'ヒ'),
+    ((5u8, 0b00010100u16), 'ミ'),
+    ((3u8, 0b00000011u16), 'リ'),
+    ((5u8, 0b00010010u16), 'ヰ'),
+    ((3u8, 0b00000100u16), 'ウ'),
+    ((4u8, 0b00001000u16), 'ク'),
+    ((5u8, 0b00010111u16), 'ス'),
+    ((4u8, 0b00000110u16), 'ツ'),
+    ((4u8, 0b00000000u16), 'ヌ'),
+    ((4u8, 0b00000011u16), 'フ'),
+    ((1u8, 0b00000001u16), 'ム'),
+    ((5u8, 0b00011001u16), 'ユ'),
+    ((5u8, 0b00001101u16), 'ル'),
+    ((5u8, 0b00011101u16), 'エ'),
+    ((4u8, 0b00001101u16), 'ケ'),
+    ((5u8, 0b00001110u16), 'セ'),
+    ((5u8, 0b00011010u16), 'テ'),
+    ((4u8, 0b00001011u16), 'ネ'),
+    ((1u8, 0b00000000u16), 'ヘ'),
+    ((5u8, 0b00010001u16), 'メ'),
+    ((3u8, 0b00000111u16), 'レ'),
+    ((5u8, 0b00000110u16), 'ヱ'),
+    ((5u8, 0b00000010u16), 'オ'),
+    ((4u8, 0b00001111u16), 'コ'),
+    ((4u8, 0b00000111u16), 'ソ'),
+    ((5u8, 0b00000100u16), 'ト'),
+    ((4u8, 0b00001100u16), 'ノ'),
+    ((3u8, 0b00000001u16), 'ホ'),
+    ((5u8, 0b00001001u16), 'モ'),
+    ((2u8, 0b00000011u16), 'ヨ'),
+    ((4u8, 0b00001010u16), 'ロ'),
+    ((4u8, 0b00001110u16), 'ヲ'),
+    ((5u8, 0b00001010u16), 'ン'),
+    ((2u8, 0b00000000u16), '゛'),
+    ((5u8, 0b00001100u16), '゜'),
+    ((5u8, 0b00010110u16), 'ー'),
+    ((6u8, 0b00101010u16), '、'),
+    ((6u8, 0b00001010u16), '」'),
+    ((6u8, 0b00101101u16), '（'),
+    ((6u8, 0b00010010u16), '）'),
+];
+
+pub static LATIN_KEY: MorseKey =
+    MorseKey::from_tables(&[&LATIN_LETTERS, &ITU_DIGITS, &ITU_PUNCTUATION]);
+pub static CYRILLIC_KEY: MorseKey =
+    MorseKey::from_tables(&[&CYRILLIC_LETTERS, &ITU_DIGITS, &ITU_PUNCTUATION]);
+pub static GREEK_KEY: MorseKey =
+    MorseKey::from_tables(&[&GREEK_LETTERS, &ITU_DIGITS, &ITU_PUNCTUATION]);
+pub static WABUN_KEY: MorseKey = MorseKey::from_tables(&[&WABUN_KANA, &ITU_DIGITS]);
+
+pub fn construct_key_for(alphabet: MorseAlphabet) -> &'static MorseKey {
+    use MorseAlphabet::*;
+    match alphabet {
+        Latin => &LATIN_KEY,
+        Cyrillic => &CYRILLIC_KEY,
+        Greek => &GREEK_KEY,
+        Wabun => &WABUN_KEY,
+    }
+}
+
+// Fails if two chars share a code, or a code is too long for a MorseKey. A
+// table known up front can instead go in a static with MorseKey::from_tables.
+pub fn construct_custom_key(
+    elements: &[(MorseSequenceSerialization, char)],
+) -> Result<MorseKey, KeyErr> {
+    let mut key = MorseKey::new();
+    for (ser, c) in elements.iter() {
+        key.insert(*ser, *c)?;
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alphabets_construct() {
+        assert_eq!(
+            LATIN_LETTERS.len() + ITU_DIGITS.len() + ITU_PUNCTUATION.len(),
+            LATIN_KEY.len()
+        );
+        assert_eq!(WABUN_KANA.len() + ITU_DIGITS.len(), WABUN_KEY.len());
+    }
+
+    #[test]
+    fn test_alphabet_lookup() {
+        use Morse::*;
+        let ser = serialize_morse(&[Dot, Dash, Dot, Dash]).unwrap();
+        assert_eq!(
+            Some(&'я'),
+            construct_key_for(MorseAlphabet::Cyrillic).get(&ser)
+        );
+        assert_eq!(
+            Some(&'ロ'),
+            construct_key_for(MorseAlphabet::Wabun).get(&ser)
+        );
+        assert_eq!(None, construct_key().get(&ser));
+
+        let ser = serialize_morse(&[Dash, Dash, Dash, Dash]).unwrap();
+        assert_eq!(
+            Some(&'χ'),
+            construct_key_for(MorseAlphabet::Greek).get(&ser)
+        );
+    }
+
+    #[test]
+    fn test_custom_key() {
+        let key = construct_custom_key(&[((1, 0), 'x'), ((1, 1), 'y')]).unwrap();
+        assert_eq!(Some(&'y'), key.get(&(1, 1)));
+        assert_eq!(
+            Err(KeyErr::CodeTaken('x')),
+            construct_custom_key(&[((1, 0), 'x'), ((1, 0), 'y')])
+        );
+    }
+}
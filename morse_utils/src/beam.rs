@@ -0,0 +1,431 @@
+use crate::*;
+
+// Costs are in hundredths of a unit, the same scale as the timing errors the
+// beam adds up, so a model can outweigh a borderline dot/dash call.
+pub trait LanguageModel {
+    // Cost of adding next to the word so far
+    fn extend_cost(&self, word: &[char], next: char) -> Time;
+    // Cost of ending the word here
+    fn end_cost(&self, word: &[char]) -> Time;
+}
+
+// Lets a converter borrow its model, see MorseConverter::set_beam
+impl<T: LanguageModel + ?Sized> LanguageModel for &T {
+    fn extend_cost(&self, word: &[char], next: char) -> Time {
+        (**self).extend_cost(word, next)
+    }
+    fn end_cost(&self, word: &[char]) -> Time {
+        (**self).end_cost(word)
+    }
+}
+
+pub struct NoLanguageModel;
+
+impl LanguageModel for NoLanguageModel {
+    fn extend_cost(&self, _word: &[char], _next: char) -> Time {
+        0
+    }
+    fn end_cost(&self, _word: &[char]) -> Time {
+        0
+    }
+}
+
+// Charges miss_cost, once, to any word that isn't in the list
+pub struct WordListModel<'a> {
+    pub words: &'a [&'a str],
+    pub miss_cost: Time,
+}
+
+impl<'a> WordListModel<'a> {
+    fn any_match<I>(&self, word: I, whole: bool) -> bool
+    where
+        I: Iterator<Item = char> + Clone,
+    {
+        self.words.iter().any(|listed| {
+            let mut listed = listed.chars();
+            for c in word.clone() {
+                if listed.next() != Some(c) {
+                    return false;
+                }
+            }
+            !whole || listed.next().is_none()
+        })
+    }
+}
+
+impl<'a> LanguageModel for WordListModel<'a> {
+    fn extend_cost(&self, word: &[char], next: char) -> Time {
+        let was_prefix = self.any_match(word.iter().copied(), false);
+        let is_prefix = self.any_match(word.iter().copied().chain(Some(next)), false);
+        if was_prefix && !is_prefix {
+            self.miss_cost
+        } else {
+            0
+        }
+    }
+    fn end_cost(&self, word: &[char]) -> Time {
+        let is_prefix = self.any_match(word.iter().copied(), false);
+        if is_prefix && !self.any_match(word.iter().copied(), true) {
+            self.miss_cost
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Hypothesis<const W: usize> {
+    pub letter: MorseSequenceSerialization,
+    pub word: Vec<char, W>,
+    pub cost: Time,
+    pub ended: bool,
+}
+
+impl<const W: usize> Hypothesis<W> {
+    fn empty() -> Hypothesis<W> {
+        Hypothesis {
+            letter: (0, 0),
+            word: Vec::new(),
+            cost: 0,
+            ended: false,
+        }
+    }
+}
+
+// What MorseConverter::set_beam decodes through. Eight readings are plenty,
+// and a word longer than 16 chars fails to decode like a bad letter would.
+pub const BEAM_WIDTH: usize = 8;
+pub const BEAM_WORD: usize = 16;
+pub type ConverterBeam<'a> = BeamDecoder<'a, &'a dyn LanguageModel, BEAM_WIDTH, BEAM_WORD>;
+
+// Decodes a word at a time from TimedLightEvents, keeping the N cheapest
+// readings of the current word rather than committing to each event. W bounds
+// the length of a word.
+pub struct BeamDecoder<'a, M, const N: usize, const W: usize>
+where
+    M: LanguageModel,
+{
+    mkey: &'a MorseKey,
+    model: M,
+    timing: MorseTiming,
+    beam: Vec<Hypothesis<W>, N>,
+}
+
+// The model is left out, there's no comparing or printing one in general
+impl<'a, M, const N: usize, const W: usize> PartialEq for BeamDecoder<'a, M, N, W>
+where
+    M: LanguageModel,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.mkey == other.mkey && self.timing == other.timing && self.beam == other.beam
+    }
+}
+
+impl<'a, M, const N: usize, const W: usize> Eq for BeamDecoder<'a, M, N, W> where M: LanguageModel {}
+
+impl<'a, M, const N: usize, const W: usize> core::fmt::Debug for BeamDecoder<'a, M, N, W>
+where
+    M: LanguageModel,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BeamDecoder")
+            .field("timing", &self.timing)
+            .field("beam", &self.beam)
+            .finish()
+    }
+}
+
+impl<'a, M, const N: usize, const W: usize> BeamDecoder<'a, M, N, W>
+where
+    M: LanguageModel,
+{
+    pub fn new(mkey: &'a MorseKey, model: M, timing: MorseTiming) -> BeamDecoder<'a, M, N, W> {
+        let mut decoder = BeamDecoder {
+            mkey,
+            model,
+            timing,
+            beam: Vec::new(),
+        };
+        decoder.reset();
+        decoder
+    }
+
+    pub fn reset(&mut self) {
+        self.beam = Vec::new();
+        // A beam of any capacity has room for one
+        let _ = self.beam.push(Hypothesis::empty());
+    }
+
+    // For following an estimated or tracked timing. The events already in
+    // the beam keep the costs they were given.
+    pub fn set_timing(&mut self, timing: MorseTiming) {
+        self.timing = timing;
+    }
+
+    // Cheapest first
+    pub fn hypotheses(&self) -> &[Hypothesis<W>] {
+        &self.beam[..]
+    }
+
+    // Returns the best word and a space once the best reading puts a word gap here
+    pub fn push_tle<const E: usize>(
+        &mut self,
+        tle: &TimedLightEvent,
+    ) -> Result<Vec<char, E>, MorseErr> {
+        match self.push_word(tle)? {
+            Some(word) => spell_word(&word),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    // Ends the word in progress where it stands, as if a word gap had come,
+    // and returns its best reading and a space. Nothing if no word was started.
+    pub fn flush<const E: usize>(&mut self) -> Result<Vec<char, E>, MorseErr> {
+        match self.flush_word()? {
+            Some(word) => spell_word(&word),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    // The ended word as decoded, prosigns and all
+    pub(crate) fn push_word(
+        &mut self,
+        tle: &TimedLightEvent,
+    ) -> Result<Option<Vec<char, W>>, MorseErr> {
+        let mut next: Vec<Hypothesis<W>, N> = Vec::new();
+        for hyp in self.beam.iter() {
+            for mc in MORSE_CANDIDATES.iter() {
+                let unit = self.timing.unit_for(mc);
+                let err = match calc_error(tle, mc, unit) {
+                    Some(err) => err,
+                    None => continue,
+                };
+                let cost = hyp.cost + err * 100 / unit.max(1);
+                if let Some(h) = self.extend(hyp, mc_to_morse(mc)?, cost) {
+                    insert_hypothesis(&mut next, h);
+                }
+            }
+        }
+
+        if next.is_empty() {
+            self.reset();
+            return Err(MorseErr::NoHypotheses);
+        }
+
+        if next[0].ended {
+            let word = next.swap_remove(0).word;
+            self.reset();
+            Ok(Some(word))
+        } else {
+            self.beam = Vec::new();
+            for h in next.into_iter().filter(|h| !h.ended) {
+                // next has the same capacity as the beam
+                let _ = self.beam.push(h);
+            }
+            Ok(None)
+        }
+    }
+
+    pub(crate) fn flush_word(&mut self) -> Result<Option<Vec<char, W>>, MorseErr> {
+        let best = self
+            .beam
+            .iter()
+            .filter_map(|hyp| self.extend(hyp, Morse::WordSpace, hyp.cost))
+            .min_by_key(|h| h.cost);
+        self.reset();
+        match best {
+            Some(h) if h.word.is_empty() => Ok(None),
+            Some(h) => Ok(Some(h.word)),
+            None => Err(MorseErr::NoHypotheses),
+        }
+    }
+
+    fn extend(&self, hyp: &Hypothesis<W>, morse: Morse, cost: Time) -> Option<Hypothesis<W>> {
+        use Morse::*;
+        let mut h = hyp.clone();
+        h.cost = cost;
+        match morse {
+            Dot | Dash => {
+                let (len, rep) = h.letter;
+                if len >= MAX_CODE_LEN {
+                    return None;
+                }
+                let bit = if morse == Dash { 1 << len } else { 0 };
+                h.letter = (len + 1, rep | bit);
+            }
+            TinySpace => (),
+            LetterSpace | WordSpace => {
+                if h.letter.0 > 0 {
+                    let c = *self.mkey.get(&h.letter)?;
+                    h.cost += self.model.extend_cost(&h.word, c);
+                    h.word.push(c).ok()?;
+                    h.letter = (0, 0);
+                }
+                if morse == WordSpace && !h.word.is_empty() {
+                    h.cost += self.model.end_cost(&h.word);
+                    h.ended = true;
+                }
+            }
+        }
+        Some(h)
+    }
+}
+
+// Spells out prosigns the way produce_chars does, and adds the word's space
+fn spell_word<const E: usize>(word: &[char]) -> Result<Vec<char, E>, MorseErr> {
+    let mut out = Vec::new();
+    for c in word.iter() {
+        match prosign_name(*c) {
+            Some(name) => {
+                for c in name.chars() {
+                    out.push(c).map_err(|_| MorseErr::InputTooLarge)?;
+                }
+            }
+            None => out.push(*c).map_err(|_| MorseErr::InputTooLarge)?,
+        }
+    }
+    out.push(' ').map_err(|_| MorseErr::InputTooLarge)?;
+    Ok(out)
+}
+
+// Keeps beam sorted cheapest first, merging hypotheses that have reached the
+// same state and dropping the most expensive when full
+fn insert_hypothesis<const W: usize, const N: usize>(
+    beam: &mut Vec<Hypothesis<W>, N>,
+    h: Hypothesis<W>,
+) {
+    let same = beam
+        .iter()
+        .position(|b| b.letter == h.letter && b.word == h.word && b.ended == h.ended);
+    match same {
+        Some(i) if beam[i].cost <= h.cost => return,
+        Some(i) => {
+            beam.swap_remove(i);
+        }
+        None if beam.len() == beam.capacity() => {
+            if beam[beam.len() - 1].cost <= h.cost {
+                return;
+            }
+            beam.pop();
+        }
+        None => (),
+    }
+    // We made room above
+    let _ = beam.push(h);
+    beam.sort_unstable_by_key(|b| b.cost);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "the" with the t's dash so short that on its own it reads as a dot
+    fn helper_sloppy_the() -> Vec<TimedLightEvent, 32> {
+        let key = construct_key();
+        let mut tles: Vec<TimedLightEvent, 32> = MorseEncoder::new("the ", key)
+            .timed(20)
+            .map(|r| r.unwrap().1)
+            .collect();
+        tles[0].duration = 38;
+        tles
+    }
+
+    fn helper_decode<M: LanguageModel>(model: M) -> Vec<char, 32> {
+        let key = construct_key();
+        let mut decoder: BeamDecoder<_, 8, 16> =
+            BeamDecoder::new(key, model, MorseTiming::from_unit(20));
+        let mut out = Vec::new();
+        for tle in helper_sloppy_the().iter() {
+            let chars: Vec<char, 16> = decoder.push_tle(tle).unwrap();
+            out.extend_from_slice(&chars).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_beam_no_model() {
+        assert_eq!(&['e', 'h', 'e', ' '], &helper_decode(NoLanguageModel)[..]);
+    }
+
+    #[test]
+    fn test_beam_word_list() {
+        let model = WordListModel {
+            words: &["the", "and", "of"],
+            miss_cost: 300,
+        };
+        assert_eq!(&['t', 'h', 'e', ' '], &helper_decode(model)[..]);
+    }
+
+    #[test]
+    fn test_manager_beam() {
+        let key = construct_key();
+        let model = WordListModel {
+            words: &["the", "and", "of"],
+            miss_cost: 300,
+        };
+        let mut samples = crate::tests::helper_text_samples("the", key, MorseTiming::from_unit(20));
+        // The t's dash cut short as in helper_sloppy_the, and the last mark
+        // ended without starting another
+        for sample in samples[1..].iter_mut() {
+            sample.sample_time -= 22;
+        }
+        samples.last_mut().unwrap().intensity = 100;
+
+        for beam in [false, true].iter() {
+            let mut manager: MorseManager<128, 64, 64, 64> =
+                MorseManager::new(500, MorseUnitTimeDecision::EstimateProvided(20));
+            if *beam {
+                manager.set_beam(Some(&model));
+            }
+            let mut out: Vec<char, 16> = Vec::new();
+            for sample in samples.iter() {
+                manager.add_sample(*sample).unwrap();
+                let chars: Vec<char, 8> = manager.produce_chars().unwrap();
+                out.extend_from_slice(&chars).unwrap();
+            }
+            // The last word only comes out of the beam with the flush
+            if *beam {
+                assert!(out.is_empty());
+            }
+            manager.flush().unwrap();
+            let chars: Vec<char, 8> = manager.produce_chars().unwrap();
+            out.extend_from_slice(&chars).unwrap();
+            let expected: &[char] = match beam {
+                true => &['t', 'h', 'e', ' '],
+                false => &['e', 'h', 'e', ' '],
+            };
+            assert_eq!(expected, &out[..]);
+        }
+    }
+
+    #[test]
+    fn test_beam_flush() {
+        let key = construct_key();
+        let mut decoder: BeamDecoder<_, 8, 16> =
+            BeamDecoder::new(key, NoLanguageModel, MorseTiming::from_unit(20));
+        let nothing: Vec<char, 8> = decoder.flush().unwrap();
+        assert!(nothing.is_empty());
+        // "the" without its word gap
+        let tles = helper_sloppy_the();
+        for tle in tles[..tles.len() - 1].iter() {
+            let chars: Vec<char, 8> = decoder.push_tle(tle).unwrap();
+            assert!(chars.is_empty());
+        }
+        let chars: Vec<char, 8> = decoder.flush().unwrap();
+        assert_eq!(&['e', 'h', 'e', ' '], &chars[..]);
+        assert_eq!(1, decoder.hypotheses().len());
+    }
+
+    #[test]
+    fn test_word_list_costs() {
+        let model = WordListModel {
+            words: &["the"],
+            miss_cost: 300,
+        };
+        assert_eq!(0, model.extend_cost(&['t'], 'h'));
+        assert_eq!(300, model.extend_cost(&['t'], 'x'));
+        assert_eq!(0, model.extend_cost(&['t', 'x'], 'y'));
+        assert_eq!(300, model.end_cost(&['t', 'h']));
+        assert_eq!(0, model.end_cost(&['t', 'h', 'e']));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::*;
+
+use core::fmt;
+
+// Recorded sensor samples, stored as text so they can be read and diffed. A
+// header of '#' lines says how to read the rows after it:
+//
+//     #morse-capture 1
+//     #period-ms 1
+//     #width 10
+//     #polarity light-high
+//     #meta sensor phototransistor
+//     900
+//     898
+//
+// With "#timed" instead of "#period-ms", each row is a sample time in ms and
+// then an intensity. The width and polarity lines can be left out for 16
+// bits and light-high.
+pub const CAPTURE_MAGIC: &str = "#morse-capture";
+pub const CAPTURE_VERSION: u32 = 1;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CaptureTiming {
+    // A sample every this many ms, starting from 0
+    Periodic(Time),
+    // Each row has its own sample time
+    Timed,
+}
+
+// Which way the raw reading moves when the light comes on. The decoder
+// expects brighter to be higher, so light-low readings are flipped.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Polarity {
+    LightHigh,
+    LightLow,
+}
+
+impl Polarity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Polarity::LightHigh => "light-high",
+            Polarity::LightLow => "light-low",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Polarity> {
+        [Polarity::LightHigh, Polarity::LightLow]
+            .iter()
+            .copied()
+            .find(|p| p.name() == name)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CaptureHeader {
+    pub timing: CaptureTiming,
+    // Bits in a raw reading, 1 to 16
+    pub width: u8,
+    pub polarity: Polarity,
+}
+
+// Line numbers count from 1, header included
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CaptureErr {
+    NotACapture,
+    UnsupportedVersion(u32),
+    BadHeaderLine(usize),
+    NoTiming,
+    BadRow(usize),
+    ReadingTooWide(usize),
+    // The index of a metadata pair that wouldn't read back the same
+    BadMetadata(usize),
+    WriteFailed,
+}
+
+impl CaptureHeader {
+    // What the bare one-intensity-per-line dumps like fastcar.txt were taken
+    // with, given the period they were sampled at
+    pub fn legacy(period: Time) -> CaptureHeader {
+        CaptureHeader {
+            timing: CaptureTiming::Periodic(period),
+            width: 16,
+            polarity: Polarity::LightHigh,
+        }
+    }
+
+    pub fn max_reading(&self) -> u16 {
+        (u32::MAX >> (32 - self.width.clamp(1, 16) as u32)) as u16
+    }
+
+    fn intensity_of(&self, reading: u16) -> Option<LightIntensity> {
+        match reading > self.max_reading() {
+            true => None,
+            false => Some(match self.polarity {
+                Polarity::LightHigh => reading,
+                Polarity::LightLow => self.max_reading() - reading,
+            }),
+        }
+    }
+
+    fn reading_of(&self, intensity: LightIntensity) -> u16 {
+        let intensity = intensity.min(self.max_reading());
+        match self.polarity {
+            Polarity::LightHigh => intensity,
+            Polarity::LightLow => self.max_reading() - intensity,
+        }
+    }
+
+    // Keys have to be one word and values one line with nothing around it to
+    // trim, as CaptureReader reads them. Nothing is written if any of them
+    // aren't.
+    pub fn write<W: fmt::Write>(
+        &self,
+        w: &mut W,
+        metadata: &[(&str, &str)],
+    ) -> Result<(), CaptureErr> {
+        let bad = metadata.iter().position(|(key, value)| {
+            key.is_empty()
+                || key.contains(char::is_whitespace)
+                || value.contains(['\n', '\r'])
+                || value.trim() != *value
+        });
+        if let Some(i) = bad {
+            return Err(CaptureErr::BadMetadata(i));
+        }
+        self.write_fields(w, metadata)
+            .map_err(|_| CaptureErr::WriteFailed)
+    }
+
+    fn write_fields<W: fmt::Write>(&self, w: &mut W, metadata: &[(&str, &str)]) -> fmt::Result {
+        writeln!(w, "{} {}", CAPTURE_MAGIC, CAPTURE_VERSION)?;
+        match self.timing {
+            CaptureTiming::Periodic(period) => writeln!(w, "#period-ms {}", period)?,
+            CaptureTiming::Timed => writeln!(w, "#timed")?,
+        }
+        writeln!(w, "#width {}", self.width)?;
+        writeln!(w, "#polarity {}", self.polarity.name())?;
+        metadata
+            .iter()
+            .try_for_each(|(key, value)| writeln!(w, "#meta {} {}", key, value))
+    }
+
+    // The row for a sample. With a period the sample time isn't written, so
+    // samples need to be written in order with none missing.
+    pub fn write_sample<W: fmt::Write>(
+        &self,
+        w: &mut W,
+        sample: &SampledLightIntensity,
+    ) -> fmt::Result {
+        let reading = self.reading_of(sample.intensity);
+        match self.timing {
+            CaptureTiming::Periodic(_) => writeln!(w, "{}", reading),
+            CaptureTiming::Timed => writeln!(w, "{} {}", sample.sample_time, reading),
+        }
+    }
+}
+
+// Reads a capture's header, then its rows one sample at a time
+pub struct CaptureReader<'a> {
+    header: CaptureHeader,
+    header_text: &'a str,
+    rows: core::str::Lines<'a>,
+    line: usize,
+    samples: Time,
+}
+
+impl<'a> CaptureReader<'a> {
+    pub fn new(text: &'a str) -> Result<CaptureReader<'a>, CaptureErr> {
+        let header_len = text
+            .split_inclusive('\n')
+            .take_while(|l| l.starts_with('#'))
+            .map(str::len)
+            .sum();
+        let header_text = &text[..header_len];
+
+        let mut magic = header_text.lines().next().unwrap_or("").split_whitespace();
+        match (magic.next(), magic.next(), magic.next()) {
+            (Some(CAPTURE_MAGIC), Some(version), None) => {
+                let version = version.parse().map_err(|_| CaptureErr::BadHeaderLine(1))?;
+                if version != CAPTURE_VERSION {
+                    return Err(CaptureErr::UnsupportedVersion(version));
+                }
+            }
+            _ => return Err(CaptureErr::NotACapture),
+        }
+
+        let mut timing = None;
+        let mut header = CaptureHeader::legacy(1);
+        for (i, l) in header_text.lines().enumerate().skip(1) {
+            let bad = CaptureErr::BadHeaderLine(i + 1);
+            let mut fields = l[1..].split_whitespace();
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some("period-ms"), Some(period), None) => {
+                    timing = Some(CaptureTiming::Periodic(period.parse().map_err(|_| bad)?))
+                }
+                (Some("timed"), None, None) => timing = Some(CaptureTiming::Timed),
+                (Some("width"), Some(width), None) => match width.parse() {
+                    Ok(width) if (1..=16).contains(&width) => header.width = width,
+                    _ => return Err(bad),
+                },
+                (Some("polarity"), Some(name), None) => {
+                    header.polarity = Polarity::from_name(name).ok_or(bad)?
+                }
+                (Some("meta"), Some(_), _) => (),
+                _ => return Err(bad),
+            }
+        }
+        header.timing = timing.ok_or(CaptureErr::NoTiming)?;
+
+        let mut reader = CaptureReader::legacy(&text[header_len..], header);
+        reader.header_text = header_text;
+        reader.line = header_text.lines().count();
+        Ok(reader)
+    }
+
+    // Rows with no header in front of them, read as the given header says
+    pub fn legacy(text: &'a str, header: CaptureHeader) -> CaptureReader<'a> {
+        CaptureReader {
+            header,
+            header_text: "",
+            rows: text.lines(),
+            line: 0,
+            samples: 0,
+        }
+    }
+
+    pub fn header(&self) -> CaptureHeader {
+        self.header
+    }
+
+    // The key and the rest of the line from each "#meta" line. Keys are one
+    // word, values can have spaces.
+    pub fn metadata(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.header_text.lines().filter_map(|l| {
+            let mut fields = l[1..].split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some("meta"), Some(key)) => {
+                    let rest = l[1..].trim_start()["meta".len()..].trim_start();
+                    Some((key, rest[key.len()..].trim()))
+                }
+                _ => None,
+            }
+        })
+    }
+
+    fn read_row(&self, row: &str) -> Option<SampledLightIntensity> {
+        let mut fields = row.split_whitespace();
+        let sample_time = match self.header.timing {
+            CaptureTiming::Periodic(period) => self.samples * period,
+            CaptureTiming::Timed => fields.next()?.parse().ok()?,
+        };
+        let reading = fields.next()?.parse().ok()?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(SampledLightIntensity {
+            sample_time,
+            intensity: reading,
+        })
+    }
+}
+
+impl<'a> Iterator for CaptureReader<'a> {
+    type Item = Result<SampledLightIntensity, CaptureErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let row = self.rows.next()?;
+            self.line += 1;
+            if row.trim().is_empty() {
+                continue;
+            }
+            let mut sample = match self.read_row(row) {
+                Some(sample) => sample,
+                None => return Some(Err(CaptureErr::BadRow(self.line))),
+            };
+            sample.intensity = match self.header.intensity_of(sample.intensity) {
+                Some(intensity) => intensity,
+                None => return Some(Err(CaptureErr::ReadingTooWide(self.line))),
+            };
+            self.samples += 1;
+            return Some(Ok(sample));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::{String, Vec};
+
+    const CAPTURE: &str = "#morse-capture 1
+#timed
+#width 10
+#polarity light-low
+#meta sensor photoresistor on PA0
+#meta note
+#meta   spaced   out  
+
+5 1000
+30 23
+";
+
+    #[test]
+    fn test_read_capture() {
+        let reader = CaptureReader::new(CAPTURE).unwrap();
+        assert_eq!(
+            CaptureHeader {
+                timing: CaptureTiming::Timed,
+                width: 10,
+                polarity: Polarity::LightLow,
+            },
+            reader.header()
+        );
+        let meta: Vec<_, 4> = reader.metadata().collect();
+        assert_eq!(
+            &[
+                ("sensor", "photoresistor on PA0"),
+                ("note", ""),
+                ("spaced", "out")
+            ],
+            &meta[..]
+        );
+
+        let samples: Result<Vec<_, 4>, _> = reader.collect();
+        let samples = samples.unwrap();
+        assert_eq!(
+            &[
+                SampledLightIntensity {
+                    sample_time: 5,
+                    intensity: 23,
+                },
+                SampledLightIntensity {
+                    sample_time: 30,
+                    intensity: 1000,
+                }
+            ],
+            &samples[..]
+        );
+    }
+
+    #[test]
+    fn test_capture_errors() {
+        assert_eq!(
+            Some(CaptureErr::NotACapture),
+            CaptureReader::new("900\n").err()
+        );
+        assert_eq!(
+            Some(CaptureErr::UnsupportedVersion(2)),
+            CaptureReader::new("#morse-capture 2\n#timed\n").err()
+        );
+        assert_eq!(
+            Some(CaptureErr::NoTiming),
+            CaptureReader::new("#morse-capture 1\n").err()
+        );
+        assert_eq!(
+            Some(CaptureErr::BadHeaderLine(2)),
+            CaptureReader::new("#morse-capture 1\n#width 17\n").err()
+        );
+        assert_eq!(
+            Some(CaptureErr::BadHeaderLine(3)),
+            CaptureReader::new("#morse-capture 1\n#timed\n#metadata x\n").err()
+        );
+
+        let mut reader =
+            CaptureReader::new("#morse-capture 1\n#period-ms 2\n#width 4\n15\n16\nx\n").unwrap();
+        assert_eq!(
+            Some(Ok(SampledLightIntensity {
+                sample_time: 0,
+                intensity: 15,
+            })),
+            reader.next()
+        );
+        assert_eq!(Some(Err(CaptureErr::ReadingTooWide(5))), reader.next());
+        assert_eq!(Some(Err(CaptureErr::BadRow(6))), reader.next());
+    }
+
+    #[test]
+    fn test_capture_round_trip() {
+        let legacy = "900\n100\n\n900\n";
+        let header = CaptureHeader {
+            polarity: Polarity::LightLow,
+            width: 10,
+            ..CaptureHeader::legacy(20)
+        };
+        let mut written: String<256> = String::new();
+        header
+            .write(&mut written, &[("from", "legacy.txt")])
+            .unwrap();
+        for sample in CaptureReader::legacy(legacy, CaptureHeader::legacy(20)) {
+            header.write_sample(&mut written, &sample.unwrap()).unwrap();
+        }
+        assert!(written.ends_with("#meta from legacy.txt\n123\n923\n123\n"));
+
+        // Keys and values that wouldn't read back, nothing written for them
+        for metadata in [
+            [("from", "legacy.txt"), ("two words", "x")],
+            [("from", "legacy.txt"), ("", "x")],
+            [("from", "legacy.txt"), ("note", "two\nlines")],
+            [("from", "legacy.txt"), ("note", " padded")],
+            [("from", "legacy.txt"), ("note", "padded\t")],
+        ]
+        .iter()
+        {
+            let mut rejected: String<256> = String::new();
+            assert_eq!(
+                Err(CaptureErr::BadMetadata(1)),
+                header.write(&mut rejected, metadata)
+            );
+            assert!(rejected.is_empty());
+        }
+
+        let reader = CaptureReader::new(&written).unwrap();
+        assert_eq!(header, reader.header());
+        let times: Result<Vec<_, 4>, _> = reader
+            .map(|s| s.map(|s| (s.sample_time, s.intensity)))
+            .collect();
+        assert_eq!(&[(0, 900), (20, 100), (40, 900)], &times.unwrap()[..]);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::*;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CutoffTrackerConfig {
+    // Each sample moves a level 1/smoothing of the way back toward it
+    pub smoothing: u32,
+    // Levels closer than this (say over a long pause) leave the cutoffs alone
+    pub min_spread: LightIntensity,
+}
+
+// Follows the light and dark intensity levels as the ambient light changes.
+// Each level jumps straight out to a new extreme and then drifts back toward
+// the samples, and the cutoffs sit a quarter of the way in from either level,
+// as with calc_digital_cutoffs.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CutoffTracker {
+    config: CutoffTrackerConfig,
+    // Scaled up by 256 so slow drifts don't round away to nothing
+    light_level: u32,
+    dark_level: u32,
+    cuts: IntensityCutoffs,
+}
+
+impl CutoffTracker {
+    // Starts from the levels the cutoffs were worked out from
+    pub fn new(cuts: IntensityCutoffs, config: CutoffTrackerConfig) -> CutoffTracker {
+        let low = cuts.low as u32;
+        let high = cuts.high.max(cuts.low) as u32;
+        let spread = 2 * (high - low);
+        let dark_level = low.saturating_sub(spread / 4);
+        CutoffTracker {
+            config,
+            light_level: (dark_level + spread) << 8,
+            dark_level: dark_level << 8,
+            cuts,
+        }
+    }
+
+    pub fn cutoffs(&self) -> IntensityCutoffs {
+        self.cuts
+    }
+
+    pub fn config(&self) -> CutoffTrackerConfig {
+        self.config
+    }
+
+    pub fn observe(&mut self, intensity: LightIntensity) -> IntensityCutoffs {
+        let smoothing = self.config.smoothing.max(1);
+        let x = (intensity as u32) << 8;
+        if x > self.light_level {
+            self.light_level = x;
+        } else {
+            self.light_level -= (self.light_level - x) / smoothing;
+        }
+        if x < self.dark_level {
+            self.dark_level = x;
+        } else {
+            self.dark_level += (x - self.dark_level) / smoothing;
+        }
+
+        let dark = self.dark_level >> 8;
+        let diff = (self.light_level >> 8).saturating_sub(dark);
+        if diff >= self.config.min_spread as u32 {
+            // Both are below the largest sample seen, so fit in a LightIntensity
+            self.cuts = IntensityCutoffs {
+                low: (dark + diff / 4) as LightIntensity,
+                high: (dark + (3 * diff) / 4) as LightIntensity,
+            };
+        }
+        self.cuts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper_tracker() -> CutoffTracker {
+        CutoffTracker::new(
+            IntensityCutoffs {
+                low: 300,
+                high: 700,
+            },
+            CutoffTrackerConfig {
+                smoothing: 4,
+                min_spread: 100,
+            },
+        )
+    }
+
+    #[test]
+    fn test_tracker_follows_dimming() {
+        let mut tracker = helper_tracker();
+        for _ in 0..20 {
+            tracker.observe(450);
+            tracker.observe(50);
+        }
+        let cuts = tracker.cutoffs();
+        assert!(cuts.low > 50 && cuts.low < 200);
+        assert!(cuts.high > 200 && cuts.high < 450);
+    }
+
+    #[test]
+    fn test_tracker_holds_through_pause() {
+        let mut tracker = helper_tracker();
+        for _ in 0..100 {
+            tracker.observe(100);
+        }
+        // The levels have met, but the cutoffs close in no further than
+        // min_spread allows
+        let cuts = tracker.cutoffs();
+        assert!(cuts.low >= 100 + 100 / 4);
+        assert!(cuts.high >= 100 + 3 * 100 / 4);
+        assert!(cuts.high < 700);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::*;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct DeglitchConfig {
+    // Shorter marks are noise in the middle of a gap
+    pub min_mark_ms: Time,
+    // Shorter gaps are dropouts in the middle of a mark
+    pub min_gap_ms: Time,
+}
+
+// Sits between intensities_to_tles and the decoder, folding events too short
+// to be real back into the event around them. The last event is held back
+// until the next real one shows it can't grow any more.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Deglitcher {
+    config: DeglitchConfig,
+    pending: Option<TimedLightEvent>,
+}
+
+impl Deglitcher {
+    pub fn new(config: DeglitchConfig) -> Deglitcher {
+        Deglitcher {
+            config,
+            pending: None,
+        }
+    }
+
+    pub fn is_glitch(&self, tle: &TimedLightEvent) -> bool {
+        let min = match tle.light_state {
+            LightState::Light => self.config.min_mark_ms,
+            LightState::Dark => self.config.min_gap_ms,
+        };
+        tle.duration < min
+    }
+
+    // Returns the previous event once tle shows it has ended
+    pub fn push(&mut self, tle: TimedLightEvent) -> Option<TimedLightEvent> {
+        let glitch = self.is_glitch(&tle);
+        // Only the first event can be held back as a glitch, with nothing
+        // before it to fold into
+        let leading_glitch = self.pending.is_some_and(|p| self.is_glitch(&p));
+        match &mut self.pending {
+            None => {
+                self.pending = Some(tle);
+                None
+            }
+            // So it goes into the event after it instead
+            Some(pending) if leading_glitch => {
+                pending.light_state = tle.light_state;
+                pending.duration += tle.duration;
+                None
+            }
+            // Either the event after a glitch, or the glitch itself
+            Some(pending) if pending.light_state == tle.light_state || glitch => {
+                pending.duration += tle.duration;
+                None
+            }
+            Some(_) => self.pending.replace(tle),
+        }
+    }
+
+    pub fn pending(&self) -> Option<&TimedLightEvent> {
+        self.pending.as_ref()
+    }
+
+    // The held event, for when no more are coming. A leading glitch that
+    // nothing came after is dropped.
+    pub fn take_pending(&mut self) -> Option<TimedLightEvent> {
+        let pending = self.pending.take()?;
+        match self.is_glitch(&pending) {
+            true => None,
+            false => Some(pending),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::Vec;
+
+    fn helper_tle(light_state: LightState, duration: Time) -> TimedLightEvent {
+        TimedLightEvent {
+            light_state,
+            duration,
+        }
+    }
+
+    #[test]
+    fn test_deglitch() {
+        use LightState::*;
+        let mut deglitcher = Deglitcher::new(DeglitchConfig {
+            min_mark_ms: 10,
+            min_gap_ms: 5,
+        });
+        let events = [
+            helper_tle(Light, 60),
+            helper_tle(Dark, 3),
+            helper_tle(Light, 20),
+            helper_tle(Dark, 50),
+            helper_tle(Light, 4),
+            helper_tle(Dark, 30),
+            helper_tle(Light, 20),
+        ];
+        let mut out: Vec<TimedLightEvent, 8> = events
+            .iter()
+            .filter_map(|tle| deglitcher.push(*tle))
+            .collect();
+        out.extend(deglitcher.take_pending());
+
+        assert_eq!(
+            &[
+                helper_tle(Light, 83),
+                helper_tle(Dark, 84),
+                helper_tle(Light, 20)
+            ],
+            &out[..]
+        );
+    }
+
+    #[test]
+    fn test_deglitch_leading() {
+        use LightState::*;
+        let mut deglitcher = Deglitcher::new(DeglitchConfig {
+            min_mark_ms: 10,
+            min_gap_ms: 5,
+        });
+        let events = [
+            helper_tle(Light, 4),
+            helper_tle(Dark, 50),
+            helper_tle(Light, 20),
+            helper_tle(Dark, 60),
+            helper_tle(Light, 60),
+        ];
+        let mut out: Vec<TimedLightEvent, 8> = events
+            .iter()
+            .filter_map(|tle| deglitcher.push(*tle))
+            .collect();
+        out.extend(deglitcher.take_pending());
+
+        // The spike would otherwise have come out as a dot
+        assert_eq!(
+            &[
+                helper_tle(Dark, 54),
+                helper_tle(Light, 20),
+                helper_tle(Dark, 60),
+                helper_tle(Light, 60)
+            ],
+            &out[..]
+        );
+
+        // With nothing after it, it goes altogether
+        assert_eq!(None, deglitcher.push(helper_tle(Light, 4)));
+        assert_eq!(None, deglitcher.take_pending());
+    }
+}
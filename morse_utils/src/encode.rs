@@ -0,0 +1,233 @@
+use crate::*;
+
+pub fn morse_to_mc(morse: Morse) -> MorseCandidate {
+    use Morse::*;
+    let (light_state, units) = match morse {
+        Dot => (LightState::Light, 1),
+        Dash => (LightState::Light, 3),
+        TinySpace => (LightState::Dark, 1),
+        LetterSpace => (LightState::Dark, 3),
+        WordSpace => (LightState::Dark, 7),
+    };
+    MorseCandidate { light_state, units }
+}
+
+pub fn morse_to_tle(morse: Morse, unit_millis: Time) -> TimedLightEvent {
+    morse_to_tle_timed(morse, MorseTiming::from_unit(unit_millis))
+}
+
+pub fn morse_to_tle_timed(morse: Morse, timing: MorseTiming) -> TimedLightEvent {
+    let mc = morse_to_mc(morse);
+    TimedLightEvent {
+        light_state: mc.light_state,
+        duration: mc.units * timing.unit_for(&mc),
+    }
+}
+
+pub fn lookup_serialization(mkey: &MorseKey, c: char) -> Option<MorseSequenceSerialization> {
+    // Keys hold lowercase letters, but only use the lowercase if it is one char
+    let mut lower = c.to_lowercase();
+    let c = match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    };
+    mkey.serialization_of(c)
+}
+
+// Turns text into the Morse symbols the decoder expects to see: elements
+// separated by TinySpace, letters by LetterSpace and any run of whitespace
+// after the first letter collapsed into a single WordSpace. Letters between
+// '<' and '>' are run together as a prosign, so "<SK>" sends ...-.-
+pub struct MorseEncoder<'a> {
+    chars: core::str::Chars<'a>,
+    mkey: &'a MorseKey,
+    letter: Option<(MorseSequenceSerialization, u8)>,
+    pending: Option<Morse>,
+    last: Option<Morse>,
+    in_prosign: bool,
+    join_next: bool,
+}
+
+impl<'a> MorseEncoder<'a> {
+    pub fn new(text: &'a str, mkey: &'a MorseKey) -> MorseEncoder<'a> {
+        MorseEncoder {
+            chars: text.chars(),
+            mkey,
+            letter: None,
+            pending: None,
+            last: None,
+            in_prosign: false,
+            join_next: false,
+        }
+    }
+
+    pub fn timed(
+        self,
+        unit_millis: Time,
+    ) -> impl Iterator<Item = Result<(Morse, TimedLightEvent), MorseErr>> + 'a {
+        self.timed_with(MorseTiming::from_unit(unit_millis))
+    }
+
+    pub fn timed_with(
+        self,
+        timing: MorseTiming,
+    ) -> impl Iterator<Item = Result<(Morse, TimedLightEvent), MorseErr>> + 'a {
+        self.map(move |m| m.map(|m| (m, morse_to_tle_timed(m, timing))))
+    }
+
+    fn emit(&mut self, morse: Morse) -> Option<Result<Morse, MorseErr>> {
+        self.last = Some(morse);
+        Some(Ok(morse))
+    }
+}
+
+impl<'a> Iterator for MorseEncoder<'a> {
+    type Item = Result<Morse, MorseErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use Morse::*;
+        if let Some(m) = self.pending.take() {
+            return self.emit(m);
+        }
+
+        loop {
+            if let Some(((len, rep), index)) = self.letter {
+                if index < len {
+                    self.letter = Some(((len, rep), index + 1));
+                    let element = if rep & (1 << index) != 0 { Dash } else { Dot };
+                    if index > 0 {
+                        self.pending = Some(element);
+                        return self.emit(TinySpace);
+                    }
+                    return self.emit(element);
+                }
+                self.letter = None;
+            }
+
+            let c = self.chars.next()?;
+            if c == '<' || c == '>' {
+                self.in_prosign = c == '<';
+                self.join_next = false;
+                continue;
+            }
+            if c.is_whitespace() {
+                // Nothing to space out before the first letter
+                if self.last == Some(WordSpace) || self.last.is_none() {
+                    continue;
+                }
+                return self.emit(WordSpace);
+            }
+
+            match lookup_serialization(self.mkey, c) {
+                None => return Some(Err(MorseErr::UnencodableChar(c))),
+                Some(ser) => {
+                    self.letter = Some((ser, 0));
+                    let join = self.join_next;
+                    self.join_next = self.in_prosign;
+                    if let Some(Dot) | Some(Dash) = self.last {
+                        return self.emit(if join { TinySpace } else { LetterSpace });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helper_decode_text;
+    use heapless::Vec;
+
+    #[test]
+    fn test_encode_morse() {
+        use Morse::*;
+        let key = construct_key();
+        let morses: Result<Vec<_, 32>, _> = MorseEncoder::new(" \tB  e ", key).collect();
+
+        assert_eq!(
+            &[Dash, TinySpace, Dot, TinySpace, Dot, TinySpace, Dot, WordSpace, Dot, WordSpace],
+            &morses.unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn test_encode_unknown() {
+        let key = construct_key();
+        let mut encoder = MorseEncoder::new("e#", key);
+        assert_eq!(Some(Ok(Morse::Dot)), encoder.next());
+        assert_eq!(Some(Err(MorseErr::UnencodableChar('#'))), encoder.next());
+        assert_eq!(None, encoder.next());
+    }
+
+    #[test]
+    fn test_encode_prosign() {
+        use Morse::*;
+        let key = construct_key();
+        let morses: Result<Vec<_, 32>, _> = MorseEncoder::new("e<SK>", key).collect();
+
+        assert_eq!(
+            &[
+                Dot,
+                LetterSpace,
+                Dot,
+                TinySpace,
+                Dot,
+                TinySpace,
+                Dot,
+                TinySpace,
+                Dash,
+                TinySpace,
+                Dot,
+                TinySpace,
+                Dash
+            ],
+            &morses.unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn test_encode_alphabet() {
+        use Morse::*;
+        let key = construct_key_for(MorseAlphabet::Greek);
+        let morses: Result<Vec<_, 32>, _> = MorseEncoder::new("ΩΕ", key).collect();
+
+        assert_eq!(
+            &[Dot, TinySpace, Dash, TinySpace, Dash, LetterSpace, Dot],
+            &morses.unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn test_encode_farnsworth() {
+        let key = construct_key();
+        let timing = MorseTiming::farnsworth(20, 10);
+        let tles: Result<Vec<_, 32>, _> = MorseEncoder::new("ee e", key)
+            .timed_with(timing)
+            .map(|r| r.map(|(_, tle)| tle.duration))
+            .collect();
+
+        assert_eq!(&[60, 3 * 217, 60, 7 * 217, 60], &tles.unwrap()[..]);
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let key = construct_key();
+        let mut converter: MorseConverter<128, 128, 128> = MorseConverter::new(
+            0,
+            MorseUnitTimeDecision::EstimateProvided(20),
+            IntensityCutoffs {
+                low: 200,
+                high: 800,
+            },
+            None,
+        );
+
+        let timing = MorseTiming::from_unit(20);
+        let vec = helper_decode_text(&mut converter, "be 73 <SK> ", key, timing).unwrap();
+        assert_eq!(
+            &['b', 'e', ' ', '7', '3', ' ', '<', 'S', 'K', '>', ' '],
+            &vec[..]
+        );
+    }
+}
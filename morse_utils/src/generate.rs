@@ -0,0 +1,338 @@
+use crate::*;
+
+// How a synthetic sender and sensor behave. SignalConfig::clean gives perfect
+// timing and a steady light, for the other fields to be set against.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct SignalConfig {
+    pub timing: MorseTiming,
+    // A dash's length in hundredths of a dot, 300 by the book. Hand keyers
+    // often drift toward 250 or 350.
+    pub dash_ratio: Time,
+    // Hundredths of a unit each mark is stretched by, taken back off the gap
+    // after it. Negative for light keying.
+    pub weight: Time,
+    // Each event is lengthened or shortened by up to this percent of itself
+    pub jitter_percent: Time,
+    pub sample_period_ms: Time,
+    pub dark: LightIntensity,
+    pub light: LightIntensity,
+    // Each sample is off by up to this much either way
+    pub noise: LightIntensity,
+    // How far both levels move each second, as the ambient light changes
+    pub drift_per_s: i32,
+    // Chance out of 1000 each sample has of starting a dropout, where the
+    // sensor reads dark whatever the light is doing
+    pub dropout_per_mille: u32,
+    pub dropout_ms: Time,
+    // Darkness before the text and after it
+    pub pad_ms: Time,
+    pub seed: u64,
+}
+
+impl SignalConfig {
+    pub fn clean(timing: MorseTiming) -> SignalConfig {
+        SignalConfig {
+            timing,
+            dash_ratio: 300,
+            weight: 0,
+            jitter_percent: 0,
+            sample_period_ms: 5,
+            dark: 100,
+            light: 900,
+            noise: 0,
+            drift_per_s: 0,
+            dropout_per_mille: 0,
+            dropout_ms: 0,
+            pad_ms: 150,
+            seed: 0,
+        }
+    }
+}
+
+// xorshift64*, which is plenty for test signals and the same on every target
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct SignalRng(u64);
+
+impl SignalRng {
+    fn new(seed: u64) -> SignalRng {
+        // Any seed, 0 included, makes a nonzero state
+        SignalRng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // From -range to range
+    fn spread(&mut self, range: i64) -> i64 {
+        match range > 0 {
+            true => (self.next() % (2 * range as u64 + 1)) as i64 - range,
+            false => 0,
+        }
+    }
+
+    fn chance(&mut self, per_mille: u32) -> bool {
+        self.next() % 1000 < per_mille as u64
+    }
+}
+
+// Text sent the way SignalConfig says, sampled into the intensities a
+// MorseManager would see. The same config and seed always give the same
+// samples.
+pub struct SignalGenerator<'a> {
+    morses: MorseEncoder<'a>,
+    config: SignalConfig,
+    // Separate streams, so turning up the noise doesn't change the timing
+    timing_rng: SignalRng,
+    sample_rng: SignalRng,
+    time: Time,
+    end_ms: Time,
+    state: LightState,
+    padded: bool,
+    dropout_until: Time,
+}
+
+impl<'a> SignalGenerator<'a> {
+    // Fails up front on text the key can't send
+    pub fn new(
+        text: &'a str,
+        mkey: &'a MorseKey,
+        config: SignalConfig,
+    ) -> Result<SignalGenerator<'a>, MorseErr> {
+        MorseEncoder::new(text, mkey).try_for_each(|m| m.map(|_| ()))?;
+        Ok(SignalGenerator {
+            morses: MorseEncoder::new(text, mkey),
+            config: SignalConfig {
+                sample_period_ms: config.sample_period_ms.max(1),
+                ..config
+            },
+            timing_rng: SignalRng::new(config.seed),
+            sample_rng: SignalRng::new(!config.seed),
+            time: 0,
+            end_ms: config.pad_ms,
+            state: LightState::Dark,
+            padded: false,
+            dropout_until: 0,
+        })
+    }
+
+    fn shape(&mut self, morse: Morse) -> TimedLightEvent {
+        let mut tle = morse_to_tle_timed(morse, self.config.timing);
+        if morse == Morse::Dash {
+            tle.duration = tle.duration * self.config.dash_ratio / 300;
+        }
+        let weight = self.config.weight * self.config.timing.unit_ms / 100;
+        tle.duration += match tle.light_state {
+            LightState::Light => weight,
+            LightState::Dark => -weight,
+        };
+        let jitter = self.timing_rng.spread(self.config.jitter_percent);
+        tle.duration += tle.duration * jitter / 100;
+        tle.duration = tle.duration.max(1);
+        tle
+    }
+
+    fn next_event(&mut self) -> Option<TimedLightEvent> {
+        match self.morses.next() {
+            // Checked in new
+            Some(morse) => Some(self.shape(morse.ok()?)),
+            None if !self.padded => {
+                self.padded = true;
+                Some(TimedLightEvent {
+                    light_state: LightState::Dark,
+                    duration: self.config.pad_ms,
+                })
+            }
+            None => None,
+        }
+    }
+
+    fn intensity(&mut self) -> LightIntensity {
+        let c = &self.config;
+        if self.dropout_until <= self.time && self.sample_rng.chance(c.dropout_per_mille) {
+            self.dropout_until = self.time + c.dropout_ms;
+        }
+        let level = match (self.state, self.time < self.dropout_until) {
+            (LightState::Light, false) => c.light,
+            _ => c.dark,
+        };
+        let drift = c.drift_per_s as i64 * self.time / 1000;
+        // Two draws add up to noise that bunches toward 0
+        let half = c.noise as i64 / 2;
+        let noise = self.sample_rng.spread(half) + self.sample_rng.spread(c.noise as i64 - half);
+        (level as i64 + drift + noise).clamp(0, LightIntensity::MAX as i64) as LightIntensity
+    }
+}
+
+impl<'a> Iterator for SignalGenerator<'a> {
+    type Item = SampledLightIntensity;
+
+    fn next(&mut self) -> Option<SampledLightIntensity> {
+        while self.time >= self.end_ms {
+            let tle = self.next_event()?;
+            self.end_ms += tle.duration;
+            self.state = tle.light_state;
+        }
+        let sample = SampledLightIntensity {
+            sample_time: self.time,
+            intensity: self.intensity(),
+        };
+        self.time += self.config.sample_period_ms;
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    const TEXT: &str = "the quick brown fox jumps over the lazy dog ";
+
+    fn helper_generate(text: &str, config: SignalConfig) -> std::vec::Vec<SampledLightIntensity> {
+        SignalGenerator::new(text, construct_key(), config)
+            .unwrap()
+            .collect()
+    }
+
+    fn helper_stress(config: SignalConfig, tracking: bool) -> std::string::String {
+        let mut manager: MorseManager<512, 64, 64, 256> = MorseManager::new(
+            (config.dark + config.light) / 2,
+            MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
+                guess_after_this_many_tles: 12,
+                max_guess_ms: 150,
+                min_guess_ms: 20,
+            }),
+        );
+        manager.set_replacement(Some('?'));
+        // Left as "<HH>", so the text can be scored as it comes out
+        manager.set_erase_on_error_signal(false);
+        if tracking {
+            manager.set_cutoff_tracking(Some(CutoffTrackerConfig {
+                smoothing: 16,
+                min_spread: 100,
+            }));
+        }
+        let mut chars = std::string::String::new();
+        for sample in helper_generate(TEXT, config) {
+            manager.add_sample(sample).unwrap();
+            manager.produce_chars_with(|c| chars.push(c)).unwrap();
+        }
+        manager.flush().unwrap();
+        manager.produce_chars_with(|c| chars.push(c)).unwrap();
+        chars
+    }
+
+    // How many chars have to be inserted, removed or changed to turn one
+    // into the other, so a dropped or split letter only costs where it is
+    fn helper_edit_distance(a: &str, b: &str) -> usize {
+        let b: std::vec::Vec<char> = b.chars().collect();
+        let mut row: std::vec::Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for (j, cb) in b.iter().enumerate() {
+                let changed = diagonal + (ca != *cb) as usize;
+                diagonal = row[j + 1];
+                row[j + 1] = changed.min(row[j] + 1).min(diagonal + 1);
+            }
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn test_clean_signal() {
+        let config = SignalConfig::clean(MorseTiming::from_unit(20));
+        let samples = helper_generate("e e", config);
+        // The padding, a dot, a word gap, a dot and the padding again
+        assert_eq!((150 + 20 + 140 + 20 + 150) / 5, samples.len());
+        assert_eq!(145, samples[29].sample_time);
+        assert_eq!(100, samples[29].intensity);
+        assert_eq!(900, samples[30].intensity);
+        assert_eq!(900, samples[33].intensity);
+        assert_eq!(100, samples[34].intensity);
+
+        assert_eq!(
+            Some(MorseErr::UnencodableChar('#')),
+            SignalGenerator::new("e#", construct_key(), config).err()
+        );
+    }
+
+    #[test]
+    fn test_seeded_signal() {
+        let config = SignalConfig {
+            jitter_percent: 20,
+            noise: 100,
+            dropout_per_mille: 10,
+            dropout_ms: 15,
+            ..SignalConfig::clean(MorseTiming::from_unit(40))
+        };
+        let first = helper_generate("paris ", config);
+        assert_eq!(first, helper_generate("paris ", config));
+        let reseeded = helper_generate("paris ", SignalConfig { seed: 1, ..config });
+        assert_ne!(first, reseeded);
+        assert!(first.iter().all(|s| (0..=1000).contains(&s.intensity)));
+
+        // Noise doesn't change the timing
+        let quiet = helper_generate("paris ", SignalConfig { noise: 0, ..config });
+        assert_eq!(first.len(), quiet.len());
+    }
+
+    #[test]
+    fn test_weighting() {
+        let config = SignalConfig {
+            dash_ratio: 350,
+            weight: 25,
+            ..SignalConfig::clean(MorseTiming::from_unit(20))
+        };
+        let samples = helper_generate("t", config);
+        let lit = samples.iter().filter(|s| s.intensity == 900).count();
+        // 70ms of dash and 5 more of weight
+        assert_eq!(75 / 5, lit);
+    }
+
+    #[test]
+    fn test_manager_stress() {
+        let base = SignalConfig {
+            jitter_percent: 15,
+            dash_ratio: 320,
+            noise: 200,
+            ..SignalConfig::clean(MorseTiming::from_wpm(20))
+        };
+        for seed in 0..16 {
+            let config = SignalConfig { seed, ..base };
+            assert_eq!(TEXT, helper_stress(config, false), "seed {}", seed);
+
+            // The light fades by over half, so the cutoffs have to follow it.
+            // The tracker jumps out to every new extreme, so this only holds
+            // without noise.
+            let fading = SignalConfig {
+                drift_per_s: -15,
+                noise: 0,
+                ..config
+            };
+            assert_eq!(TEXT, helper_stress(fading, true), "fading seed {}", seed);
+        }
+
+        // Dropouts long enough to break up a dash can't all be recovered
+        // from, but most of the text should still come through
+        // A dropped letter costs one edit, not the rest of the line
+        assert_eq!(1, helper_edit_distance("the qick brown", "the quick brown"));
+        let mut wrong = 0;
+        for seed in 0..16 {
+            let config = SignalConfig {
+                seed,
+                dropout_per_mille: 2,
+                dropout_ms: 25,
+                ..base
+            };
+            let chars = helper_stress(config, false);
+            wrong += helper_edit_distance(&chars, TEXT);
+        }
+        assert!(wrong * 10 <= 16 * TEXT.len() * 3);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::*;
+
+// The longest code a MorseKey, and so a letter, can have
+pub const MAX_CODE_LEN: u8 = 16;
+// Codes up to this long get a slot of their own
+const SHORT_CODE_LEN: u8 = 8;
+const SHORT_SLOTS: usize = (1 << (SHORT_CODE_LEN + 1)) - 1;
+// Longer codes are rare, so are kept in a short list instead
+const LONG_CODES: usize = 16;
+const SLOTS: usize = SHORT_SLOTS + LONG_CODES;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum KeyErr {
+    // Another char already has the code
+    CodeTaken(char),
+    // Longer than MAX_CODE_LEN
+    CodeTooLong,
+    // Empty, or with bits set past its length
+    BadCode,
+    // Every place for a code longer than 8 is taken
+    TooManyLongCodes,
+}
+
+// The decode tree laid out a level per length, so a code's slot comes straight
+// from its MorseSequenceSerialization. Built by a const fn, so the alphabets
+// are worked out at compile time and live in flash rather than RAM.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct MorseKey {
+    short: [Option<char>; SHORT_SLOTS],
+    long: [Option<(MorseSequenceSerialization, char)>; LONG_CODES],
+    // The slots in use sorted by char and then slot, the long ones numbered
+    // on from the short, so encoding can look codes up by char
+    by_char: [u16; SLOTS],
+    codes: usize,
+}
+
+enum Place {
+    Short(usize),
+    Long(usize),
+}
+
+const fn is_valid(ser: MorseSequenceSerialization) -> bool {
+    let (len, bits) = ser;
+    len > 0 && len <= MAX_CODE_LEN && (len == MAX_CODE_LEN || bits >> len == 0)
+}
+
+const fn short_slot(ser: MorseSequenceSerialization) -> usize {
+    (1 << ser.0) - 1 + ser.1 as usize
+}
+
+// The other way, with the length found from the level the slot is on
+const fn short_ser(slot: usize) -> MorseSequenceSerialization {
+    let mut len = 0;
+    while (2 << len) - 1 <= slot {
+        len += 1;
+    }
+    (len as u8, (slot + 1 - (1 << len)) as u16)
+}
+
+impl MorseKey {
+    pub const fn new() -> MorseKey {
+        MorseKey {
+            short: [None; SHORT_SLOTS],
+            long: [None; LONG_CODES],
+            by_char: [0; SLOTS],
+            codes: 0,
+        }
+    }
+
+    // Where a new code would go, if it is valid and not taken
+    const fn place(&self, ser: MorseSequenceSerialization) -> Result<Place, KeyErr> {
+        if ser.0 > MAX_CODE_LEN {
+            return Err(KeyErr::CodeTooLong);
+        }
+        if !is_valid(ser) {
+            return Err(KeyErr::BadCode);
+        }
+        if ser.0 <= SHORT_CODE_LEN {
+            let s = short_slot(ser);
+            return match self.short[s] {
+                None => Ok(Place::Short(s)),
+                Some(c) => Err(KeyErr::CodeTaken(c)),
+            };
+        }
+        let mut i = 0;
+        while i < LONG_CODES {
+            match self.long[i] {
+                None => return Ok(Place::Long(i)),
+                Some((taken, c)) if taken.0 == ser.0 && taken.1 == ser.1 => {
+                    return Err(KeyErr::CodeTaken(c))
+                }
+                Some(_) => i += 1,
+            }
+        }
+        Err(KeyErr::TooManyLongCodes)
+    }
+
+    const fn slot_entry(&self, slot: usize) -> Option<(MorseSequenceSerialization, char)> {
+        if slot < SHORT_SLOTS {
+            match self.short[slot] {
+                Some(c) => Some((short_ser(slot), c)),
+                None => None,
+            }
+        } else {
+            self.long[slot - SHORT_SLOTS]
+        }
+    }
+
+    // The first place in by_char at or after (c, slot)
+    const fn by_char_position(&self, c: char, slot: usize) -> usize {
+        let (mut lo, mut hi) = (0, self.codes);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let at = self.by_char[mid] as usize;
+            let before = match self.slot_entry(at) {
+                Some((_, at_c)) => {
+                    (at_c as u32) < (c as u32) || (at_c as u32 == c as u32 && at < slot)
+                }
+                None => false,
+            };
+            if before {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    // Like from_tables, for codes only known at run time
+    pub const fn insert(&mut self, ser: MorseSequenceSerialization, c: char) -> Result<(), KeyErr> {
+        let slot = match self.place(ser) {
+            Ok(Place::Short(s)) => s,
+            Ok(Place::Long(l)) => SHORT_SLOTS + l,
+            Err(e) => return Err(e),
+        };
+        let at = self.by_char_position(c, slot);
+        let mut i = self.codes;
+        while i > at {
+            self.by_char[i] = self.by_char[i - 1];
+            i -= 1;
+        }
+        self.by_char[at] = slot as u16;
+        self.codes += 1;
+        if slot < SHORT_SLOTS {
+            self.short[slot] = Some(c);
+        } else {
+            self.long[slot - SHORT_SLOTS] = Some((ser, c));
+        }
+        Ok(())
+    }
+
+    // Fails the build if used for a static and two chars share a code, or a
+    // code doesn't fit
+    pub const fn from_tables(tables: &[&[(MorseSequenceSerialization, char)]]) -> MorseKey {
+        let mut key = MorseKey::new();
+        let mut t = 0;
+        while t < tables.len() {
+            let mut i = 0;
+            while i < tables[t].len() {
+                let (ser, c) = tables[t][i];
+                match key.insert(ser, c) {
+                    Ok(()) => (),
+                    Err(KeyErr::CodeTaken(_)) => panic!("two chars share a code"),
+                    Err(KeyErr::CodeTooLong) => panic!("a code is longer than MAX_CODE_LEN"),
+                    Err(KeyErr::BadCode) => panic!("a code is empty or has stray bits"),
+                    Err(KeyErr::TooManyLongCodes) => panic!("too many codes longer than 8"),
+                }
+                i += 1;
+            }
+            t += 1;
+        }
+        key
+    }
+
+    pub fn get(&self, ser: &MorseSequenceSerialization) -> Option<&char> {
+        if !is_valid(*ser) {
+            None
+        } else if ser.0 <= SHORT_CODE_LEN {
+            self.short[short_slot(*ser)].as_ref()
+        } else {
+            self.long
+                .iter()
+                .flatten()
+                .find(|(taken, _)| taken == ser)
+                .map(|(_, c)| c)
+        }
+    }
+
+    // The code for c, for encoding with the same key used to decode. A char
+    // with more than one code gets the first one iter gives.
+    pub fn serialization_of(&self, c: char) -> Option<MorseSequenceSerialization> {
+        let at = self.by_char_position(c, 0);
+        if at == self.codes {
+            return None;
+        }
+        match self.slot_entry(self.by_char[at] as usize) {
+            Some((ser, found)) if found == c => Some(ser),
+            _ => None,
+        }
+    }
+
+    // The short codes by length and then bits, then the long ones as they
+    // were added
+    pub fn iter(&self) -> impl Iterator<Item = (MorseSequenceSerialization, char)> + '_ {
+        let short = (1..=SHORT_CODE_LEN).flat_map(move |len| {
+            (0..(1u16 << len)).filter_map(move |bits| {
+                let ser = (len, bits);
+                self.get(&ser).map(|c| (ser, *c))
+            })
+        });
+        short.chain(self.long.iter().flatten().copied())
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for MorseKey {
+    fn default() -> MorseKey {
+        MorseKey::new()
+    }
+}
+
+// Only the codes that are set, rather than all of the slots
+impl core::fmt::Debug for MorseKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TWO: MorseKey = MorseKey::from_tables(&[&[((1, 0), 'x')], &[((8, 0xff), 'y')]]);
+
+    #[test]
+    fn test_key_slots() {
+        assert_eq!(Some(&'x'), TWO.get(&(1, 0)));
+        assert_eq!(Some(&'y'), TWO.get(&(8, 0xff)));
+        assert_eq!(None, TWO.get(&(1, 1)));
+        // Bits past the length aren't part of the code
+        assert_eq!(None, TWO.get(&(1, 2)));
+        assert_eq!(None, TWO.get(&(0, 0)));
+        assert_eq!(Some((8, 0xff)), TWO.serialization_of('y'));
+        assert_eq!(2, TWO.len());
+
+        let mut key = TWO;
+        assert_eq!(Err(KeyErr::CodeTaken('x')), key.insert((1, 0), 'z'));
+        assert_eq!(Err(KeyErr::CodeTooLong), key.insert((17, 0), 'z'));
+        assert_eq!(Err(KeyErr::BadCode), key.insert((0, 0), 'z'));
+        assert_eq!(Err(KeyErr::BadCode), key.insert((1, 2), 'z'));
+        assert_eq!(Ok(()), key.insert((2, 3), 'z'));
+        assert_eq!(Some(&'z'), key.get(&(2, 3)));
+    }
+
+    #[test]
+    fn test_serialization_of() {
+        // The same code a scan through iter would find first
+        for key in [&LATIN_KEY, &CYRILLIC_KEY, &GREEK_KEY, &WABUN_KEY].iter() {
+            for (_, c) in key.iter() {
+                let scanned = key.iter().find(|(_, v)| *v == c).map(|(k, _)| k);
+                assert_eq!(scanned, key.serialization_of(c));
+            }
+        }
+        assert_eq!(None, LATIN_KEY.serialization_of('#'));
+
+        let mut key = MorseKey::new();
+        key.insert((16, 1), 'a').unwrap();
+        key.insert((3, 5), 'a').unwrap();
+        key.insert((2, 1), 'b').unwrap();
+        assert_eq!(Some((3, 5)), key.serialization_of('a'));
+        assert_eq!(Some((2, 1)), key.serialization_of('b'));
+        assert_eq!(None, key.serialization_of('c'));
+    }
+
+    #[test]
+    fn test_long_codes() {
+        let mut key = MorseKey::new();
+        for i in 0..LONG_CODES as u16 {
+            key.insert((16, i), 'x').unwrap();
+        }
+        assert_eq!(Err(KeyErr::CodeTaken('x')), key.insert((16, 0), 'y'));
+        // The list is full
+        assert_eq!(Err(KeyErr::TooManyLongCodes), key.insert((9, 0), 'y'));
+        assert_eq!(Some(&'x'), key.get(&(16, 3)));
+        assert_eq!(None, key.get(&(16, LONG_CODES as u16)));
+        assert_eq!(LONG_CODES, key.len());
+    }
+
+    #[test]
+    fn test_every_slot_distinct() {
+        let mut key = MorseKey::new();
+        for len in 1..=SHORT_CODE_LEN {
+            for bits in 0..(1u16 << len) {
+                key.insert((len, bits), 'x').unwrap();
+            }
+        }
+        assert_eq!(SHORT_SLOTS - 1, key.len());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::*;
+
+// Builds up a letter's MorseSequenceSerialization one element at a time as
+// they arrive, so nothing is held onto or copied while waiting for the letter
+// to end. Problems with the letter are noted as they turn up, but only
+// reported once it ends, as they would be from the whole letter at once.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct LetterAssembler {
+    // Everything since the last letter ended, tiny spaces included
+    elements: u8,
+    marks: u8,
+    tiny_spaces: u8,
+    // A dash sets the bit for its place, as in serialize_morse
+    bits: u16,
+    // Two marks have run together with no tiny space between them
+    bad_spacing: bool,
+    // A word space ended the last letter, and its ' ' hasn't been taken
+    space_due: bool,
+}
+
+impl LetterAssembler {
+    pub fn new() -> LetterAssembler {
+        LetterAssembler::default()
+    }
+
+    // Hands back the letter once a letter or word space ends it
+    pub fn push(&mut self, morse: Morse, mkey: &MorseKey) -> Result<Option<char>, MorseErr> {
+        use Morse::*;
+        match morse {
+            Dot | Dash => {
+                if morse == Dash && self.marks < MAX_CODE_LEN {
+                    self.bits |= 1 << self.marks;
+                }
+                self.marks = self.marks.saturating_add(1);
+                if self.marks > self.tiny_spaces.saturating_add(1) {
+                    self.bad_spacing = true;
+                }
+            }
+            TinySpace => self.tiny_spaces = self.tiny_spaces.saturating_add(1),
+            LetterSpace => return self.finish(mkey),
+            WordSpace => {
+                self.space_due = true;
+                return self.finish(mkey);
+            }
+        }
+        self.elements = self.elements.saturating_add(1);
+        Ok(None)
+    }
+
+    // True once after each word space, for the ' ' it stands for
+    pub fn take_space(&mut self) -> bool {
+        core::mem::replace(&mut self.space_due, false)
+    }
+
+    // Nothing of a letter has come in since the last one ended
+    pub fn is_empty(&self) -> bool {
+        self.elements == 0
+    }
+
+    fn finish(&mut self, mkey: &MorseKey) -> Result<Option<char>, MorseErr> {
+        let letter = core::mem::replace(
+            self,
+            LetterAssembler {
+                space_due: self.space_due,
+                ..LetterAssembler::default()
+            },
+        );
+        if letter.is_empty() {
+            Ok(None)
+        } else if letter.elements >= 2 * MAX_CODE_LEN {
+            Err(MorseErr::InputTooLarge)
+        } else if letter.bad_spacing {
+            Err(MorseErr::InvalidLetterTinySpacing)
+        } else if letter.marks > MAX_CODE_LEN {
+            Err(MorseErr::InputTooLarge)
+        } else if letter.marks == 0 {
+            Err(MorseErr::EmptyInput)
+        } else {
+            let ser = (letter.marks, letter.bits);
+            let c = mkey.get(&ser).ok_or(MorseErr::UnknownChar(ser))?;
+            Ok(Some(*c))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Morse::*;
+
+    fn helper_push_all(
+        assembler: &mut LetterAssembler,
+        morses: &[Morse],
+        key: &MorseKey,
+    ) -> Result<Option<char>, MorseErr> {
+        let mut last = Ok(None);
+        for m in morses {
+            last = assembler.push(*m, key);
+        }
+        last
+    }
+
+    #[test]
+    fn test_assembler_errors() {
+        let key = construct_key();
+        let mut assembler = LetterAssembler::new();
+
+        let run_together = [Dot, Dash, TinySpace, Dot, LetterSpace];
+        assert_eq!(
+            Err(MorseErr::InvalidLetterTinySpacing),
+            helper_push_all(&mut assembler, &run_together, key)
+        );
+        assert!(assembler.is_empty());
+
+        let only_spaces = [TinySpace, TinySpace, WordSpace];
+        assert_eq!(
+            Err(MorseErr::EmptyInput),
+            helper_push_all(&mut assembler, &only_spaces, key)
+        );
+        // The word space still counts after the bad letter
+        assert!(assembler.take_space());
+        assert!(!assembler.take_space());
+
+        let mut too_long = [TinySpace; 34];
+        for i in 0..17 {
+            too_long[2 * i] = Dot;
+        }
+        too_long[33] = LetterSpace;
+        assert_eq!(
+            Err(MorseErr::InputTooLarge),
+            helper_push_all(&mut assembler, &too_long, key)
+        );
+        // Fits, but isn't in the key
+        assert_eq!(
+            Err(MorseErr::UnknownChar((16, 0))),
+            helper_push_all(&mut assembler, &too_long[2..], key)
+        );
+
+        let a = [Dot, TinySpace, Dash, LetterSpace];
+        assert_eq!(Ok(Some('a')), helper_push_all(&mut assembler, &a, key));
+    }
+}
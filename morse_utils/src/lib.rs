@@ -11,13 +11,41 @@ pub enum Morse {
 
 extern crate heapless;
 
+mod alphabet;
+mod beam;
+mod capture;
+mod cutoffs;
+mod deglitch;
+mod encode;
+mod generate;
+mod key;
+mod letter;
+mod notation;
+mod observer;
+mod render;
+mod tone;
+mod wav;
+pub use alphabet::*;
+pub use beam::*;
+pub use capture::*;
+pub use cutoffs::*;
+pub use deglitch::*;
+pub use encode::*;
+pub use generate::*;
+pub use key::*;
+pub use letter::*;
+pub use notation::*;
+pub use observer::*;
+pub use render::*;
+pub use tone::*;
+pub use wav::*;
+
 use core::convert::TryFrom;
+use core::mem::size_of;
 
-use heapless::consts::*;
+use heapless::spsc::Consumer;
 use heapless::spsc::Queue;
 use heapless::Vec;
-use heapless::{spsc::Consumer, ArrayLength};
-use heapless::{spsc::Producer, FnvIndexMap};
 
 pub type Time = i64;
 pub type LightIntensity = u16;
@@ -34,13 +62,16 @@ pub enum MorseErr {
     InputTooLarge,
     MorseInputCrossesLetterBound(Morse),
     QueueBug,
-    UnknownChar((u8, u8)),
+    UnknownChar(MorseSequenceSerialization),
     EmptyInput,
     ConsumeLogicBug,
     InvalidMorseCandidate(MorseCandidate),
     FailedTLEConversion(ConvertErrs),
     InvalidLetterTinySpacing,
     CalcDigitalFailed(CalcDigitalCutoffsErrs),
+    UnencodableChar(char),
+    UnparsableNotation(char),
+    NoHypotheses,
 }
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -61,7 +92,7 @@ pub struct TimedLightEvent {
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
-pub struct ConsumeSamplesInfo<C: heapless::ArrayLength<TimedLightEvent>> {
+pub struct ConsumeSamplesInfo<const C: usize> {
     pub tles: Vec<TimedLightEvent, C>,
     pub state: (Time, LightState),
 }
@@ -72,238 +103,1241 @@ pub struct DeriveUnitTimeConfig {
     pub max_guess_ms: Time,
 }
 
+// Farnsworth timing sends characters at one speed and stretches the letter
+// and word gaps to bring the overall speed down. Those gaps are measured in
+// spacing units, everything else in the element unit.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub struct MorseTiming {
+    pub unit_ms: Time,
+    pub spacing_ms: Time,
+}
+
+impl MorseTiming {
+    pub fn from_unit(unit_ms: Time) -> MorseTiming {
+        MorseTiming {
+            unit_ms,
+            spacing_ms: unit_ms,
+        }
+    }
+
+    // Standard PARIS timing
+    pub fn from_wpm(wpm: Time) -> MorseTiming {
+        MorseTiming::from_unit(1200 / wpm.max(1))
+    }
+
+    // The ARRL Farnsworth formula, with character and effective speeds in wpm
+    pub fn farnsworth(char_wpm: Time, effective_wpm: Time) -> MorseTiming {
+        let c = char_wpm.max(1);
+        let s = effective_wpm.max(1);
+        if s >= c {
+            return MorseTiming::from_wpm(c);
+        }
+        MorseTiming {
+            unit_ms: 1200 / c,
+            spacing_ms: (60_000 * c - 37_200 * s) / (19 * c * s),
+        }
+    }
+
+    pub fn unit_for(&self, mc: &MorseCandidate) -> Time {
+        match mc {
+            MorseCandidate {
+                light_state: LightState::Dark,
+                units,
+            } if *units > 1 => self.spacing_ms,
+            _ => self.unit_ms,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub struct DeriveTimingConfig {
+    pub derive: DeriveUnitTimeConfig,
+    pub max_spacing_ms: Time,
+}
+
+// Counts bad events over each window of classified events: ones classified
+// with less than min_confidence, and letters that fail to decode. More than
+// max_bad of them in a window calls for recalibrating.
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub struct RelockConfig {
+    pub window: u32,
+    pub max_bad: u32,
+    pub min_confidence: u8,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Copy)]
+pub struct TrackUnitTimeConfig {
+    pub derive: DeriveUnitTimeConfig,
+    // Each event moves the estimate 1/smoothing of the way to the unit it implies
+    pub smoothing: Time,
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum MorseUnitTimeDecision {
     // How many tles should we wait for until we guess?j
     EstimateToBeDetermined(DeriveUnitTimeConfig),
     EstimateProvided(Time),
+    // Guess like EstimateToBeDetermined, then keep following the sender
+    EstimateTracked(TrackUnitTimeConfig, Option<Time>),
+    // Separate element and spacing units, for Farnsworth timing
+    TimingToBeDetermined(DeriveTimingConfig),
+    TimingProvided(MorseTiming),
+}
+
+impl MorseUnitTimeDecision {
+    pub fn estimate(&self) -> Option<Time> {
+        self.timing().map(|t| t.unit_ms)
+    }
+
+    pub fn timing(&self) -> Option<MorseTiming> {
+        use MorseUnitTimeDecision::*;
+        match self {
+            EstimateToBeDetermined(_) | TimingToBeDetermined(_) => None,
+            EstimateProvided(unit_ms) => Some(MorseTiming::from_unit(*unit_ms)),
+            EstimateTracked(_, unit_ms) => unit_ms.map(MorseTiming::from_unit),
+            TimingProvided(timing) => Some(*timing),
+        }
+    }
 }
 
+// The converter's capacities, then WARMUP for the samples held back to work
+// out the cutoffs from. They are replayed into the converter a sample queue at
+// a time once it is started, so SAMPLES can be smaller than WARMUP.
 #[derive(PartialEq, Eq, Debug)]
-pub struct MorseManager<C, D>
-where
-    C: ArrayLength<SampledLightIntensity> + ArrayLength<TimedLightEvent> + ArrayLength<Morse>,
-    D: ArrayLength<SampledLightIntensity>,
-{
-    converter: Option<MorseConverter<C>>,
-    sample_buf: Vec<SampledLightIntensity, D>,
+pub struct MorseManager<
+    'a,
+    const SAMPLES: usize,
+    const TLES: usize,
+    const MORSES: usize,
+    const WARMUP: usize,
+    O = NoObserver,
+> {
+    // Made up front and started over in place once the warm up is done, so
+    // it never has to be built on the stack and moved
+    converter: MorseConverter<'a, SAMPLES, TLES, MORSES, O>,
+    // The converter has been started on the warm up samples
+    calibrated: bool,
+    sample_buf: Vec<SampledLightIntensity, WARMUP>,
     span_count: u32,
     likely_middle: LightIntensity,
     likely_last_light_state: LightState,
+    // The span count at the first sample, for spotting the end of the idle
+    // stretch a stream starts with
+    idle_spans: Option<u32>,
+    // That stretch was light for longer than the warm up buffer holds, too
+    // long to be a mark
+    idle_light: bool,
     unit_time: MorseUnitTimeDecision,
 }
 
-impl<C, D> MorseManager<C, D>
-where
-    C: ArrayLength<SampledLightIntensity> + ArrayLength<TimedLightEvent> + ArrayLength<Morse>,
-    D: ArrayLength<SampledLightIntensity>,
+impl<'a, const SAMPLES: usize, const TLES: usize, const MORSES: usize, const WARMUP: usize>
+    MorseManager<'a, SAMPLES, TLES, MORSES, WARMUP>
 {
     pub fn new(
         likely_middle: LightIntensity,
         unit_time: MorseUnitTimeDecision,
-    ) -> MorseManager<C, D> {
+    ) -> MorseManager<'a, SAMPLES, TLES, MORSES, WARMUP> {
+        MorseManager::new_with_key(likely_middle, unit_time, construct_key())
+    }
+
+    pub fn new_with_key(
+        likely_middle: LightIntensity,
+        unit_time: MorseUnitTimeDecision,
+        morse_key: &'a MorseKey,
+    ) -> MorseManager<'a, SAMPLES, TLES, MORSES, WARMUP> {
+        MorseManager::new_with_observer(likely_middle, unit_time, morse_key, NoObserver)
+    }
+}
+
+impl<'a, const SAMPLES: usize, const TLES: usize, const MORSES: usize, const WARMUP: usize, O>
+    MorseManager<'a, SAMPLES, TLES, MORSES, WARMUP, O>
+where
+    O: MorseObserver,
+{
+    // The whole manager, including its converter and the warm up buffer
+    pub const RAM_BYTES: usize = size_of::<Self>();
+
+    pub fn new_with_observer(
+        likely_middle: LightIntensity,
+        unit_time: MorseUnitTimeDecision,
+        morse_key: &'a MorseKey,
+        observer: O,
+    ) -> MorseManager<'a, SAMPLES, TLES, MORSES, WARMUP, O> {
+        // The start time and cutoffs are placeholders until the warm up is done
+        let cuts = IntensityCutoffs { low: 0, high: 0 };
         MorseManager {
-            converter: None,
+            converter: MorseConverter::new_with_observer(
+                0, unit_time, cuts, None, morse_key, observer,
+            ),
+            calibrated: false,
             sample_buf: Vec::new(),
             span_count: 0,
             likely_middle,
             likely_last_light_state: LightState::Dark,
+            idle_spans: None,
+            idle_light: false,
             unit_time,
         }
     }
 
+    pub fn observer(&self) -> &O {
+        self.converter.observer()
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        self.converter.observer_mut()
+    }
+
+    pub fn set_replacement(&mut self, replacement: Option<char>) {
+        self.converter.set_replacement(replacement);
+    }
+
+    pub fn set_cutoff_tracking(&mut self, config: Option<CutoffTrackerConfig>) {
+        self.converter.set_cutoff_tracking(config);
+    }
+
+    pub fn set_deglitch(&mut self, config: Option<DeglitchConfig>) {
+        self.converter.set_deglitch(config);
+    }
+
+    pub fn set_idle_timeout(&mut self, timeout: Option<Time>) {
+        self.converter.set_idle_timeout(timeout);
+    }
+
+    pub fn set_beam(&mut self, model: Option<&'a dyn LanguageModel>) {
+        self.converter.set_beam(model);
+    }
+
+    pub fn set_erase_on_error_signal(&mut self, erase: bool) {
+        self.converter.set_erase_on_error_signal(erase);
+    }
+
+    pub fn last_erase_len(&self) -> u32 {
+        self.converter.last_erase_len()
+    }
+
+    // With relocking on, the manager resets itself when the converter finds
+    // too many bad events, after handing back what was decoded
+    pub fn set_relock(&mut self, config: Option<RelockConfig>) {
+        self.converter.set_relock(config);
+    }
+
+    // Goes back to collecting warm up samples to work out the cutoffs and
+    // unit time from. The letter and word in progress are dropped with the
+    // rest, so flush and produce first to keep them.
+    pub fn reset(&mut self) {
+        self.calibrated = false;
+        self.sample_buf.clear();
+        self.span_count = 0;
+        self.likely_last_light_state = LightState::Dark;
+        self.idle_spans = None;
+        self.idle_light = false;
+    }
+
+    fn relock_if_due(&mut self) {
+        if self.calibrated && self.converter.needs_relock() {
+            self.reset();
+            self.converter.observer.recalibrating();
+        }
+    }
+
+    // Makes do with fewer warm up light changes than usual, as no more are coming
+    pub fn flush(&mut self) -> Result<(), MorseErr> {
+        match self.ready_converter(1)? {
+            true => self.converter.flush(),
+            false => Ok(()),
+        }
+    }
+
+    // None until the warm up samples have been used to work them out
+    pub fn cutoffs(&self) -> Option<IntensityCutoffs> {
+        match self.calibrated {
+            true => Some(self.converter.cutoffs()),
+            false => None,
+        }
+    }
+
+    pub fn pop_recovered_err(&mut self) -> Option<RecoveredErr> {
+        match self.calibrated {
+            true => self.converter.pop_recovered_err(),
+            false => None,
+        }
+    }
+
+    pub fn unit_time_estimate(&self) -> Option<Time> {
+        self.timing_estimate().map(|t| t.unit_ms)
+    }
+
+    pub fn timing_estimate(&self) -> Option<MorseTiming> {
+        match self.calibrated {
+            true => self.converter.timing_estimate(),
+            false => self.unit_time.timing(),
+        }
+    }
+
     pub fn add_sample(&mut self, sli: SampledLightIntensity) -> Result<(), MorseErr> {
-        match &mut self.converter {
-            None => {
-                if sli.intensity < self.likely_middle
-                    && self.likely_last_light_state == LightState::Light
-                {
-                    self.span_count += 1;
-                    self.likely_last_light_state = LightState::Dark;
-                } else if sli.intensity > self.likely_middle
-                    && self.likely_last_light_state == LightState::Dark
-                {
-                    self.span_count += 1;
-                    self.likely_last_light_state = LightState::Light;
-                }
+        if self.calibrated {
+            return self.converter.add_sample(sli);
+        }
+        if sli.intensity < self.likely_middle && self.likely_last_light_state == LightState::Light {
+            self.span_count += 1;
+            self.likely_last_light_state = LightState::Dark;
+        } else if sli.intensity > self.likely_middle
+            && self.likely_last_light_state == LightState::Dark
+        {
+            self.span_count += 1;
+            self.likely_last_light_state = LightState::Light;
+        }
 
-                match self.sample_buf.push(sli) {
-                    Ok(_) => Ok(()),
-                    Err(_) => Err(MorseErr::InputTooLarge),
+        // A stream can start with minutes of nothing happening, which says
+        // nothing about the cutoffs. Only the end of a leading gap is kept,
+        // as the converter starts out dark anyway, and leading light that
+        // overflows the buffer is dropped.
+        let idle_spans = *self.idle_spans.get_or_insert(self.span_count);
+        if self.span_count == idle_spans {
+            match self.likely_last_light_state {
+                LightState::Dark => self.sample_buf.clear(),
+                LightState::Light if self.idle_light || self.sample_buf.is_full() => {
+                    self.idle_light = true;
+                    self.sample_buf.clear();
+                    return Ok(());
                 }
+                LightState::Light => (),
             }
-            Some(converter) => converter.add_sample(sli),
+        }
+
+        match self.sample_buf.push(sli) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(MorseErr::InputTooLarge),
         }
     }
 
-    pub fn produce_chars<E>(&mut self) -> Result<Vec<char, E>, MorseErr>
-    where
-        E: ArrayLength<char>,
-    {
-        match &mut self.converter {
-            None if self.span_count > 5 => {
-                let cuts = calc_digital_cutoffs(&self.sample_buf[..]);
-                let cuts = cuts.map_err(|e| MorseErr::CalcDigitalFailed(e))?;
-                self.converter = Some(
-                    MorseConverter::new(self.sample_buf[0].sample_time, self.unit_time, cuts, None)
-                        .map_err(|_| MorseErr::InputTooLarge)?,
-                );
-                for sli in self.sample_buf.iter() {
-                    // This unwrap is safe!!! We just explicitly set this field to some.
-                    self.converter.as_mut().unwrap().add_sample(*sli)?;
+    // The converter is only started once the warm up samples show enough
+    // light changes to work out the cutoffs. Returns whether it has been.
+    fn ready_converter(&mut self, min_spans: u32) -> Result<bool, MorseErr> {
+        if !self.calibrated && self.span_count > min_spans {
+            let cuts = match calc_digital_cutoffs(&self.sample_buf[..]) {
+                Ok(cuts) => cuts,
+                Err(e) => {
+                    let err = MorseErr::CalcDigitalFailed(e);
+                    self.converter.observer.error_raised(&err);
+                    return Err(err);
+                }
+            };
+            self.converter.observer.cutoffs_computed(cuts);
+            self.converter
+                .restart(self.sample_buf[0].sample_time, self.unit_time, cuts);
+            for sli in self.sample_buf.iter() {
+                let converter = &mut self.converter;
+                let r = match converter.samples.is_full() {
+                    true => converter.consume_samples(),
+                    false => Ok(()),
+                };
+                if let Err(err) = r.and_then(|_| converter.add_sample(*sli)) {
+                    // Left uncalibrated, to start over on the next try
+                    self.converter.observer.error_raised(&err);
+                    return Err(err);
                 }
-
-                // Would have preferred this to recurse and call self.produce_chars. The unwrap is safe
-                self.converter.as_mut().unwrap().produce_chars()
             }
-            None => Ok(Vec::new()),
-            Some(converter) => converter.produce_chars(),
+            self.calibrated = true;
         }
+        Ok(self.calibrated)
+    }
+
+    // Before a relock starts the converter over, whatever it still holds is
+    // flushed out after the chars already produced. As with the converter,
+    // the chars can include ERASE if set_erase_on_error_signal is on.
+    pub fn produce_chars<const E: usize>(&mut self) -> Result<Vec<char, E>, MorseErr> {
+        let r = match self.ready_converter(5)? {
+            true => self.converter.produce_chars().and_then(|mut chars| {
+                if self.converter.needs_relock() {
+                    self.converter.flush()?;
+                    let held: Vec<char, E> = self.converter.produce_chars()?;
+                    chars
+                        .extend_from_slice(&held)
+                        .map_err(|_| MorseErr::InputTooLarge)?;
+                }
+                Ok(chars)
+            }),
+            false => Ok(Vec::new()),
+        };
+        self.relock_if_due();
+        r
+    }
+
+    // Like produce_chars, with no Vec to fill
+    pub fn produce_chars_with<F: FnMut(char)>(&mut self, mut emit: F) -> Result<(), MorseErr> {
+        let r = match self.ready_converter(5)? {
+            true => self.converter.produce_chars_with(&mut emit).and_then(|_| {
+                if self.converter.needs_relock() {
+                    self.converter.flush()?;
+                    self.converter.produce_chars_with(&mut emit)?;
+                }
+                Ok(())
+            }),
+            false => Ok(()),
+        };
+        self.relock_if_due();
+        r
+    }
+
+    // Like produce_chars, ERASE included
+    pub fn produce_decoded<const E: usize>(&mut self) -> Result<Vec<DecodedChar, E>, MorseErr> {
+        let r = match self.ready_converter(5)? {
+            true => self.converter.produce_decoded().and_then(|mut decoded| {
+                if self.converter.needs_relock() {
+                    self.converter.flush()?;
+                    let held: Vec<DecodedChar, E> = self.converter.produce_decoded()?;
+                    decoded
+                        .extend_from_slice(&held)
+                        .map_err(|_| MorseErr::InputTooLarge)?;
+                }
+                Ok(decoded)
+            }),
+            false => Ok(Vec::new()),
+        };
+        self.relock_if_due();
+        r
     }
 }
 
+// Each stage has its own queue capacity, so they can be sized separately:
+// SAMPLES for samples waiting to become events, TLES for events waiting on
+// the timing, and MORSES for elements waiting to be assembled into letters. As with
+// heapless::spsc::Queue, each queue holds one less than its capacity.
 #[derive(PartialEq, Eq, Debug)]
-pub struct MorseConverter<C>
-where
-    C: ArrayLength<SampledLightIntensity> + ArrayLength<TimedLightEvent> + ArrayLength<Morse>,
-{
-    samples: Queue<SampledLightIntensity, C, usize>,
-    tles: Queue<TimedLightEvent, C, usize>,
-    morses: Queue<Morse, C, usize>,
-    hold_word: Queue<Morse, C, usize>,
+pub struct MorseConverter<
+    'a,
+    const SAMPLES: usize,
+    const TLES: usize,
+    const MORSES: usize,
+    O = NoObserver,
+> {
+    samples: Queue<SampledLightIntensity, SAMPLES>,
+    tles: Queue<TimedLightEvent, TLES>,
     to_tles_init: (Time, LightState),
     cuts: IntensityCutoffs,
-    morse_key: MorseKey,
+    cut_tracker: Option<CutoffTracker>,
+    deglitcher: Option<Deglitcher>,
+    morse_key: &'a MorseKey,
     dark_push_time: Option<Time>,
     unit_time: MorseUnitTimeDecision,
+    replacement: Option<char>,
+    recovered: Queue<RecoveredErr, 8>,
+    chars_produced: u32,
+    tle_starts: Queue<Time, TLES>,
+    next_tle_start: Time,
+    stamps: Queue<MorseStamp, MORSES>,
+    letter: LetterAssembler,
+    letter_confidence: u8,
+    // The marks of the letter being assembled so far
+    letter_stamp: Option<MorseStamp>,
+    // The gap a pending word break space stands for
+    gap_stamp: Option<MorseStamp>,
+    // Chars since the last space, and in the word before it, for the error
+    // signal to take back. A replacement starts both over, so the signal
+    // never reaches back past a letter that failed to decode.
+    word_chars: u32,
+    last_word_chars: u32,
+    // The char being emitted is a replacement
+    replacing: bool,
+    // The error signal stands in for a word, so the space after it goes too
+    erased: bool,
+    // Whether the error signal takes back chars with ERASE, or is spelled
+    // out like any other prosign
+    erase_on_error_signal: bool,
+    // How many chars the last ERASE handed back takes back
+    last_erase_len: u32,
+    last_morse_end: Time,
+    last_sample_time: Time,
+    idle_timeout: Option<Time>,
+    // A mark has gone by since the last word break
+    marks_since_break: bool,
+    // When the flush that called for a word break happened
+    word_break_due: Option<Time>,
+    // The gap a flush broke off is still going
+    idle: bool,
+    force_settle: bool,
+    relock: RelockCounter,
+    // Decodes a word at a time instead of a letter at a time when set
+    beam: Option<ConverterBeam<'a>>,
+    observer: O,
 }
 
-fn queue_fill_vec<T, C>(mut q: Queue<T, C, usize>) -> (Queue<T, C, usize>, Vec<T, C>)
-where
-    T: Clone + core::fmt::Debug,
-    C: ArrayLength<T>,
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct DecodedChar {
+    pub c: char,
+    // 0 to 100, from the least clear-cut timing in the letter. A letter's
+    // trailing gap counts toward it.
+    pub confidence: u8,
+    // From the start of the letter's first mark to the end of its last, or
+    // across the gap for a word break
+    pub start: Time,
+    pub end: Time,
+    // The unit time the letter was classified with
+    pub unit_ms: Time,
+    // For an ERASE, how many chars it takes back
+    pub erases: u32,
+}
+
+// A classified Morse waiting to be assembled into a letter, with where it
+// came from in the sample stream
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct MorseStamp {
+    pub morse: Morse,
+    pub confidence: u8,
+    pub start: Time,
+    pub end: Time,
+    pub unit_ms: Time,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+struct RelockCounter {
+    config: Option<RelockConfig>,
+    seen: u32,
+    bad: u32,
+    due: bool,
+}
+
+impl RelockCounter {
+    fn new(config: Option<RelockConfig>) -> RelockCounter {
+        RelockCounter {
+            config,
+            seen: 0,
+            bad: 0,
+            due: false,
+        }
+    }
+
+    fn note_bad(&mut self) {
+        if let Some(config) = self.config {
+            self.bad += 1;
+            if self.bad > config.max_bad {
+                self.due = true;
+            }
+        }
+    }
+
+    fn note_classified(&mut self, confidence: u8) {
+        if let Some(config) = self.config {
+            if confidence < config.min_confidence {
+                self.note_bad();
+            }
+            self.seen += 1;
+            if self.seen >= config.window {
+                self.seen = 0;
+                self.bad = 0;
+            }
+        }
+    }
+}
+
+// A letter that failed to decode and was replaced rather than stopping the converter
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct RecoveredErr {
+    pub err: MorseErr,
+    // How many chars had been produced before the replacement
+    pub char_index: u32,
+}
+
+fn record_err<const C: usize>(recovered: &mut Queue<RecoveredErr, C>, rec: RecoveredErr) {
+    if recovered.len() == recovered.capacity() {
+        recovered.dequeue();
+    }
+    // Can't fail, we just made room
+    let _ = recovered.enqueue(rec);
+}
+
+impl<'a, const SAMPLES: usize, const TLES: usize, const MORSES: usize>
+    MorseConverter<'a, SAMPLES, TLES, MORSES>
 {
-    let mut v = Vec::new();
-    let mut newq = Queue::new();
-    while !q.is_empty() {
-        let item = q.dequeue().unwrap();
-        v.push(item.clone()).unwrap();
-        newq.enqueue(item).unwrap();
-    }
-    (newq, v)
+    pub fn new(
+        start_time: Time,
+        unit_time: MorseUnitTimeDecision,
+        cuts: IntensityCutoffs,
+        dark_push_time: Option<Time>,
+    ) -> MorseConverter<'a, SAMPLES, TLES, MORSES> {
+        MorseConverter::new_with_key(start_time, unit_time, cuts, dark_push_time, construct_key())
+    }
+
+    pub fn new_with_key(
+        start_time: Time,
+        unit_time: MorseUnitTimeDecision,
+        cuts: IntensityCutoffs,
+        dark_push_time: Option<Time>,
+        morse_key: &'a MorseKey,
+    ) -> MorseConverter<'a, SAMPLES, TLES, MORSES> {
+        MorseConverter::new_with_observer(
+            start_time,
+            unit_time,
+            cuts,
+            dark_push_time,
+            morse_key,
+            NoObserver,
+        )
+    }
 }
 
-impl<C> MorseConverter<C>
+impl<'a, const SAMPLES: usize, const TLES: usize, const MORSES: usize, O>
+    MorseConverter<'a, SAMPLES, TLES, MORSES, O>
 where
-    C: ArrayLength<SampledLightIntensity> + ArrayLength<TimedLightEvent> + ArrayLength<Morse>,
+    O: MorseObserver,
 {
-    pub fn new(
+    // What each stage's queues take up, for checking the capacities against
+    // the target's RAM at compile time
+    pub const SAMPLES_BYTES: usize = size_of::<Queue<SampledLightIntensity, SAMPLES>>();
+    pub const TLES_BYTES: usize =
+        size_of::<Queue<TimedLightEvent, TLES>>() + size_of::<Queue<Time, TLES>>();
+    pub const MORSES_BYTES: usize = size_of::<Queue<MorseStamp, MORSES>>();
+    // The whole converter, queues included
+    pub const RAM_BYTES: usize = size_of::<Self>();
+
+    pub fn new_with_observer(
         start_time: Time,
         unit_time: MorseUnitTimeDecision,
         cuts: IntensityCutoffs,
         dark_push_time: Option<Time>,
-    ) -> Result<MorseConverter<C>, ()> {
-        Ok(MorseConverter {
+        morse_key: &'a MorseKey,
+        observer: O,
+    ) -> MorseConverter<'a, SAMPLES, TLES, MORSES, O> {
+        MorseConverter {
             samples: Queue::new(),
             tles: Queue::new(),
-            morses: Queue::new(),
-            hold_word: Queue::new(),
             cuts,
+            cut_tracker: None,
+            deglitcher: None,
             to_tles_init: (start_time, LightState::Dark),
-            morse_key: construct_key()?,
+            morse_key,
             dark_push_time,
-            unit_time: unit_time,
-        })
+            unit_time,
+            replacement: None,
+            recovered: Queue::new(),
+            chars_produced: 0,
+            tle_starts: Queue::new(),
+            next_tle_start: start_time,
+            stamps: Queue::new(),
+            letter: LetterAssembler::new(),
+            letter_confidence: 100,
+            letter_stamp: None,
+            gap_stamp: None,
+            word_chars: 0,
+            last_word_chars: 0,
+            replacing: false,
+            erased: false,
+            erase_on_error_signal: false,
+            last_erase_len: 0,
+            last_morse_end: start_time,
+            last_sample_time: start_time,
+            idle_timeout: None,
+            marks_since_break: false,
+            word_break_due: None,
+            idle: false,
+            force_settle: false,
+            relock: RelockCounter::new(None),
+            beam: None,
+            observer,
+        }
+    }
+
+    // Starts over on a new stream where new_with_observer would, without
+    // making a new converter. Keeps the settings and the observer, and drops
+    // everything else.
+    pub fn restart(
+        &mut self,
+        start_time: Time,
+        unit_time: MorseUnitTimeDecision,
+        cuts: IntensityCutoffs,
+    ) {
+        while self.samples.dequeue().is_some() {}
+        while self.tles.dequeue().is_some() {}
+        while self.tle_starts.dequeue().is_some() {}
+        while self.stamps.dequeue().is_some() {}
+        while self.recovered.dequeue().is_some() {}
+        self.to_tles_init = (start_time, LightState::Dark);
+        self.cuts = cuts;
+        self.cut_tracker = self
+            .cut_tracker
+            .map(|tracker| CutoffTracker::new(cuts, tracker.config()));
+        if let Some(deglitcher) = &mut self.deglitcher {
+            deglitcher.take_pending();
+        }
+        self.unit_time = unit_time;
+        self.chars_produced = 0;
+        self.next_tle_start = start_time;
+        self.letter = LetterAssembler::new();
+        self.letter_confidence = 100;
+        self.letter_stamp = None;
+        self.gap_stamp = None;
+        self.word_chars = 0;
+        self.last_word_chars = 0;
+        self.replacing = false;
+        self.erased = false;
+        self.last_erase_len = 0;
+        self.last_morse_end = start_time;
+        self.last_sample_time = start_time;
+        self.marks_since_break = false;
+        self.word_break_due = None;
+        self.idle = false;
+        self.force_settle = false;
+        self.relock = RelockCounter::new(self.relock.config);
+        if let Some(beam) = &mut self.beam {
+            beam.reset();
+        }
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    fn raise(&mut self, err: MorseErr) -> MorseErr {
+        self.observer.error_raised(&err);
+        self.relock.note_bad();
+        err
+    }
+
+    pub fn set_relock(&mut self, config: Option<RelockConfig>) {
+        self.relock = RelockCounter::new(config);
+    }
+
+    // Whether enough has gone wrong that the cutoffs and unit time should be
+    // worked out again. MorseManager acts on this, a lone converter can't.
+    pub fn needs_relock(&self) -> bool {
+        self.relock.due
+    }
+
+    // With a replacement set, letters that fail to decode produce it instead
+    // of an error. The errors can be read back with pop_recovered_err.
+    pub fn set_replacement(&mut self, replacement: Option<char>) {
+        self.replacement = replacement;
+    }
+
+    // With tracking on, the cutoffs follow the light and dark levels instead
+    // of staying where they started
+    pub fn set_cutoff_tracking(&mut self, config: Option<CutoffTrackerConfig>) {
+        self.cut_tracker = config.map(|config| CutoffTracker::new(self.cuts, config));
+    }
+
+    pub fn cutoffs(&self) -> IntensityCutoffs {
+        self.cuts
+    }
+
+    // With deglitching on, marks and gaps too short to be real are merged
+    // into the event around them, at the cost of holding back the latest event
+    pub fn set_deglitch(&mut self, config: Option<DeglitchConfig>) {
+        self.deglitcher = config.map(Deglitcher::new);
+    }
+
+    // Off by default, when the error signal comes out as "<HH>" and the
+    // chars before it are left alone. On, it comes out as an ERASE.
+    pub fn set_erase_on_error_signal(&mut self, erase: bool) {
+        self.erase_on_error_signal = erase;
+    }
+
+    // How many chars the last ERASE handed back takes back. produce_decoded
+    // has it on each ERASE, this is for produce_chars.
+    pub fn last_erase_len(&self) -> u32 {
+        self.last_erase_len
+    }
+
+    // With a model set, letters go through a BeamDecoder, which weighs up
+    // each word's readings against the model before settling on one. A
+    // borderline dot or dash can come out the other way if that makes a
+    // better word, but words only come out once they end.
+    pub fn set_beam(&mut self, model: Option<&'a dyn LanguageModel>) {
+        // The timing is brought up to date before each event goes in
+        let timing = self
+            .unit_time
+            .timing()
+            .unwrap_or_else(|| MorseTiming::from_unit(1));
+        self.beam = model.map(|model| BeamDecoder::new(self.morse_key, model, timing));
+    }
+
+    // A gap at least this long is treated like a flush, so the last letter
+    // comes out without waiting for the next mark
+    pub fn set_idle_timeout(&mut self, timeout: Option<Time>) {
+        self.idle_timeout = timeout;
+    }
+
+    // Closes off whatever is still open as if the sender had stopped. The
+    // next produce_chars or produce_decoded hands back the held letter and a
+    // word break, settling the timing on what has come in so far if need be.
+    pub fn flush(&mut self) -> Result<(), MorseErr> {
+        self.force_settle = true;
+        let r = self.consume_samples().and_then(|_| self.close_event());
+        r.map_err(|e| self.raise(e))
+    }
+
+    // Only the most recent errors are kept
+    pub fn pop_recovered_err(&mut self) -> Option<RecoveredErr> {
+        self.recovered.dequeue()
+    }
+
+    pub fn unit_time_estimate(&self) -> Option<Time> {
+        self.unit_time.estimate()
+    }
+    pub fn timing_estimate(&self) -> Option<MorseTiming> {
+        self.unit_time.timing()
     }
     pub fn add_sample(&mut self, sample: SampledLightIntensity) -> Result<(), MorseErr> {
         match self.samples.enqueue(sample) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.last_sample_time = sample.sample_time;
+                Ok(())
+            }
             Err(_) => Err(MorseErr::InputTooLarge),
         }
     }
+    // Each event goes straight into the tle queue, so nothing here is sized
+    // by the sample queue
     fn consume_samples(&mut self) -> Result<(), MorseErr> {
-        let r = intensities_to_tles(
-            &mut self.samples.split().1,
-            self.to_tles_init,
-            self.cuts,
-            self.dark_push_time,
-        )
-        .map_err(|e| MorseErr::FailedTLEConversion(e))?;
-        let ConsumeSamplesInfo { tles, state } = r;
-        for t in tles {
-            self.tles.enqueue(t).map_err(|_| MorseErr::InputTooLarge)?;
+        let cuts = self.cuts;
+        while let Some(sli) = self.samples.dequeue() {
+            let sample_cuts = match &mut self.cut_tracker {
+                Some(tracker) => tracker.observe(sli.intensity),
+                None => cuts,
+            };
+            if let Some(tle) = next_tle(
+                &mut self.to_tles_init,
+                sli,
+                sample_cuts,
+                self.dark_push_time,
+            ) {
+                self.enqueue_tle(tle)?;
+            }
+        }
+        if let Some(tracker) = &self.cut_tracker {
+            if tracker.cutoffs() != cuts {
+                self.cuts = tracker.cutoffs();
+                self.observer.cutoffs_computed(self.cuts);
+            }
+        }
+        Ok(())
+    }
+    fn enqueue_tle(&mut self, tle: TimedLightEvent) -> Result<(), MorseErr> {
+        let tle = match &mut self.deglitcher {
+            Some(deglitcher) => match deglitcher.push(tle) {
+                Some(tle) => tle,
+                None => return Ok(()),
+            },
+            None => tle,
+        };
+        self.enqueue_deglitched(tle)
+    }
+    fn enqueue_deglitched(&mut self, tle: TimedLightEvent) -> Result<(), MorseErr> {
+        let start = self.next_tle_start;
+        self.next_tle_start += tle.duration;
+        if self.idle {
+            self.idle = false;
+            // The rest of the gap after a flush, which already has its word break
+            if tle.light_state == LightState::Dark {
+                return Ok(());
+            }
+        }
+        if tle.light_state == LightState::Light {
+            self.marks_since_break = true;
+        }
+        self.observer.tle_produced(&tle);
+        self.tles
+            .enqueue(tle)
+            .map_err(|_| MorseErr::InputTooLarge)?;
+        self.tle_starts
+            .enqueue(start)
+            .map_err(|_| MorseErr::InputTooLarge)
+    }
+    // Ends the current event at the last sample and queues up a word break
+    fn close_event(&mut self) -> Result<(), MorseErr> {
+        let (start_time, light_state) = self.to_tles_init;
+        if light_state == LightState::Light && self.last_sample_time > start_time {
+            self.enqueue_tle(TimedLightEvent {
+                light_state,
+                duration: self.last_sample_time - start_time,
+            })?;
+        }
+        if let Some(tle) = self.deglitcher.as_mut().and_then(|d| d.take_pending()) {
+            self.enqueue_deglitched(tle)?;
+        }
+        self.to_tles_init = (self.last_sample_time, LightState::Dark);
+        self.next_tle_start = self.last_sample_time;
+        self.idle = true;
+        if self.marks_since_break {
+            self.marks_since_break = false;
+            self.word_break_due = Some(self.last_sample_time);
         }
-        self.to_tles_init = state;
         Ok(())
     }
-    fn consume_tles(&mut self, unit_ms: Time) -> Result<(), MorseErr> {
+    fn check_idle(&mut self) -> Result<(), MorseErr> {
+        let timeout = match self.idle_timeout {
+            Some(timeout) => timeout,
+            None => return Ok(()),
+        };
+        let held_mark = match self.deglitcher.as_ref().and_then(|d| d.pending()) {
+            Some(tle) => tle.light_state == LightState::Light,
+            None => false,
+        };
+        let (start_time, light_state) = self.to_tles_init;
+        if light_state == LightState::Dark
+            && (self.marks_since_break || held_mark)
+            && self.last_sample_time - start_time >= timeout
+        {
+            self.close_event()?;
+        }
+        Ok(())
+    }
+    fn enough_tles(&self, cutoff: u32) -> bool {
+        self.tles.len() as u32 >= cutoff || (self.force_settle && !self.tles.is_empty())
+    }
+    fn initial_estimate(&self, config: DeriveUnitTimeConfig) -> Result<Option<Time>, MorseErr> {
+        let DeriveUnitTimeConfig {
+            guess_after_this_many_tles: cutoff,
+            max_guess_ms: max,
+            min_guess_ms: min,
+        } = config;
+        if self.enough_tles(cutoff) {
+            let v: Vec<_, TLES> = self.tles.iter().map(|x| x.clone()).collect();
+            Ok(Some(estimate_unit_time(&v[..], min, max)?.item))
+        } else {
+            Ok(None)
+        }
+    }
+    fn consume_tles(&mut self, timing: MorseTiming) -> Result<(), MorseErr> {
+        let mut timing = timing;
         while !self.tles.is_empty() {
             let tle = self.tles.dequeue().ok_or(MorseErr::QueueBug)?;
-            let m = tle_to_best_morse(&tle, unit_ms)?;
-            self.morses
-                .enqueue(m)
-                .map_err(|_| MorseErr::InputTooLarge)?;
+            let start = self.tle_starts.dequeue().ok_or(MorseErr::QueueBug)?;
+            let scored = best_error_timed(&tle, timing)?;
+            let m = mc_to_morse(scored.item)?;
+            self.observer.morse_classified(&tle, scored, m);
+            let stamp = MorseStamp {
+                morse: m,
+                confidence: classification_confidence(&tle, timing),
+                start,
+                end: start + tle.duration,
+                unit_ms: timing.unit_for(scored.item),
+            };
+            self.relock.note_classified(stamp.confidence);
+            if let MorseUnitTimeDecision::EstimateTracked(config, _) = self.unit_time {
+                let unit_ms = track_unit_time(&tle, scored, timing.unit_ms, config);
+                timing = MorseTiming::from_unit(unit_ms);
+                self.unit_time = MorseUnitTimeDecision::EstimateTracked(config, Some(unit_ms));
+            }
+            self.enqueue_morse(stamp)?;
         }
+        if let Some(flush_time) = self.word_break_due.take() {
+            self.enqueue_morse(MorseStamp {
+                morse: Morse::WordSpace,
+                confidence: 100,
+                start: self.last_morse_end,
+                end: flush_time.max(self.last_morse_end),
+                unit_ms: timing.spacing_ms,
+            })?;
+        }
+        Ok(())
+    }
+    fn enqueue_morse(&mut self, stamp: MorseStamp) -> Result<(), MorseErr> {
+        self.stamps
+            .enqueue(stamp)
+            .map_err(|_| MorseErr::InputTooLarge)?;
+        self.last_morse_end = stamp.end;
         Ok(())
     }
-    fn consume_morses<D>(&mut self) -> Result<Vec<char, D>, MorseErr>
+    fn consume_morses_with<F>(&mut self, mut emit: F) -> Result<(), MorseErr>
     where
-        D: ArrayLength<char>,
+        F: FnMut(DecodedChar) -> Result<(), MorseErr>,
     {
-        let mut outvec = Vec::new();
-        let (_, mut producer) = self.morses.split();
-        loop {
-            //TODO! - remove the clone here
-            let (char, newqueue) = definitive_consume_morses_produce_letter(
-                &mut producer,
-                self.hold_word.clone(),
-                &self.morse_key,
-            )?;
-            self.hold_word = newqueue;
-            match char {
-                Some(c) => outvec.push(c).map_err(|_| MorseErr::InputTooLarge)?,
-                None => break,
+        while let Some(stamp) = self.stamps.dequeue() {
+            self.letter_confidence = self.letter_confidence.min(stamp.confidence);
+            match stamp.morse {
+                Morse::Dot | Morse::Dash => {
+                    self.letter_stamp = match self.letter_stamp {
+                        Some(letter) => Some(MorseStamp {
+                            end: stamp.end,
+                            unit_ms: stamp.unit_ms,
+                            ..letter
+                        }),
+                        None => Some(stamp),
+                    }
+                }
+                Morse::WordSpace => self.gap_stamp = Some(stamp),
+                Morse::TinySpace | Morse::LetterSpace => (),
+            }
+            if self.beam.is_some() {
+                self.beam_stamp(stamp, &mut emit)?;
+                continue;
+            }
+            let char = match self.letter.push(stamp.morse, self.morse_key) {
+                Ok(char) => char,
+                Err(err) => Some(self.recover(err)?),
+            };
+            if let Some(c) = char {
+                self.emit_char(c, &mut emit)?;
+            }
+            if self.letter.take_space() {
+                self.emit_char(' ', &mut emit)?;
             }
         }
-        Ok(outvec)
+        Ok(())
     }
-
-    pub fn produce_chars_with_estimate<D>(&mut self, unit_ms: i64) -> Result<Vec<char, D>, MorseErr>
-    where
-        D: ArrayLength<char>,
-    {
-        self.consume_tles(unit_ms)?;
-        self.consume_morses()
+    // The replacement for a letter or word that failed to decode, if one is set
+    fn recover(&mut self, err: MorseErr) -> Result<char, MorseErr> {
+        let replacement = self.replacement.ok_or(err)?;
+        let char_index = self.chars_produced;
+        self.observer.error_raised(&err);
+        self.relock.note_bad();
+        record_err(&mut self.recovered, RecoveredErr { err, char_index });
+        self.letter_confidence = 0;
+        self.word_chars = 0;
+        self.last_word_chars = 0;
+        self.replacing = true;
+        Ok(replacement)
     }
-
-    pub fn produce_chars<D>(&mut self) -> Result<Vec<char, D>, MorseErr>
+    // Hands the beam the event the stamp was classified from, and emits the
+    // word once the beam settles on one. Each char of it gets the word's span
+    // and its least confident timing.
+    fn beam_stamp<F>(&mut self, stamp: MorseStamp, emit: &mut F) -> Result<(), MorseErr>
     where
-        D: ArrayLength<char>,
+        F: FnMut(DecodedChar) -> Result<(), MorseErr>,
     {
-        self.consume_samples()?;
-
-        match self.unit_time {
-            MorseUnitTimeDecision::EstimateProvided(unit_ms) => {
-                // Would have preferred that this was a recursive call to this function
-                self.produce_chars_with_estimate(unit_ms)
-            }
-            MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
-                guess_after_this_many_tles: cutoff,
-                max_guess_ms: max,
-                min_guess_ms: min,
-            }) => {
-                if self.tles.len() as u32 >= cutoff {
-                    let v: Vec<_, C> = self.tles.iter().map(|x| x.clone()).collect();
-
-                    let unit_ms = estimate_unit_time(&v[..], min, max)?.item;
-                    self.unit_time = MorseUnitTimeDecision::EstimateProvided(unit_ms);
-
-                    // Would have preferred that this was a recursive call to this function
-                    self.produce_chars_with_estimate(unit_ms)
-                } else {
-                    Ok(Vec::new())
+        let timing = self.unit_time.timing();
+        let beam = match &mut self.beam {
+            Some(beam) => beam,
+            None => return Ok(()),
+        };
+        if let Some(timing) = timing {
+            beam.set_timing(timing);
+        }
+        let light_state = match stamp.morse {
+            Morse::Dot | Morse::Dash => LightState::Light,
+            _ => LightState::Dark,
+        };
+        let tle = TimedLightEvent {
+            light_state,
+            duration: stamp.end - stamp.start,
+        };
+        let mut word = beam.push_word(&tle);
+        // A flush or a long enough gap ends the word, whatever the beam made of it
+        if stamp.morse == Morse::WordSpace {
+            if let Ok(None) = word {
+                word = beam.flush_word();
+            }
+        }
+        let (word, ended): (Vec<char, BEAM_WORD>, bool) = match word {
+            Ok(Some(word)) => (word, true),
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                let replacement = self.recover(err)?;
+                let mut word = Vec::new();
+                // Room for one in any word
+                let _ = word.push(replacement);
+                (word, stamp.morse == Morse::WordSpace)
+            }
+        };
+        let word_stamp = self.letter_stamp.take();
+        let confidence = self.letter_confidence;
+        for c in word {
+            self.letter_stamp = word_stamp;
+            self.letter_confidence = confidence;
+            self.emit_char(c, emit)?;
+        }
+        if ended {
+            self.letter_stamp = None;
+            self.emit_char(' ', emit)?;
+        }
+        Ok(())
+    }
+    fn emit_char<F>(&mut self, c: char, emit: &mut F) -> Result<(), MorseErr>
+    where
+        F: FnMut(DecodedChar) -> Result<(), MorseErr>,
+    {
+        let confidence = self.letter_confidence;
+        self.letter_confidence = 100;
+        // A letter has its marks, and a word break space its gap
+        let stamp = match self.letter_stamp.take() {
+            Some(stamp) => Some(stamp),
+            None => self.gap_stamp.take(),
+        };
+        let (start, end, unit_ms) = match stamp {
+            Some(stamp) => (stamp.start, stamp.end, stamp.unit_ms),
+            None => (self.last_morse_end, self.last_morse_end, 0),
+        };
+        let erase = match c {
+            ' ' if self.erased => {
+                self.erased = false;
+                return Ok(());
+            }
+            ' ' => {
+                self.last_word_chars = self.word_chars;
+                self.word_chars = 0;
+                0
+            }
+            // Part way through a word takes back that word, otherwise the
+            // one before along with its space
+            PROSIGN_HH if self.erase_on_error_signal => {
+                let erase = match (self.word_chars, self.last_word_chars) {
+                    (0, 0) => 0,
+                    (0, last) => last + 1,
+                    (word, _) => word,
+                };
+                self.word_chars = 0;
+                self.last_word_chars = 0;
+                self.erased = true;
+                erase
+            }
+            _ => {
+                self.erased = false;
+                0
+            }
+        };
+        let erasing = c == PROSIGN_HH && self.erase_on_error_signal;
+        if erasing && erase > 0 {
+            self.last_erase_len = erase;
+        }
+        let observer = &mut self.observer;
+        let mut emit_observed = |c, erases| {
+            let decoded = DecodedChar {
+                c,
+                confidence,
+                start,
+                end,
+                unit_ms,
+                erases,
+            };
+            observer.letter_emitted(&decoded);
+            emit(decoded)
+        };
+        let produced = match (c, prosign_name(c)) {
+            // However many chars it takes back, so it always fits
+            (PROSIGN_HH, _) if erasing && erase > 0 => {
+                emit_observed(ERASE, erase)?;
+                1
+            }
+            (PROSIGN_HH, _) if erasing => 0,
+            (_, Some(name)) => {
+                for c in name.chars() {
+                    emit_observed(c, 0)?;
                 }
+                name.len() as u32
+            }
+            (_, None) => {
+                emit_observed(c, 0)?;
+                1
+            }
+        };
+        self.chars_produced += produced;
+        if c != ' ' && !erasing && !self.replacing {
+            self.word_chars += produced;
+        }
+        self.replacing = false;
+        Ok(())
+    }
+
+    fn consume_morses<const D: usize>(&mut self) -> Result<Vec<char, D>, MorseErr> {
+        let mut outvec = Vec::new();
+        self.consume_morses_with(|d| outvec.push(d.c).map_err(|_| MorseErr::InputTooLarge))?;
+        Ok(outvec)
+    }
+
+    fn consume_morses_decoded<const D: usize>(&mut self) -> Result<Vec<DecodedChar, D>, MorseErr> {
+        let mut outvec = Vec::new();
+        self.consume_morses_with(|d| outvec.push(d).map_err(|_| MorseErr::InputTooLarge))?;
+        Ok(outvec)
+    }
+
+    // Makes the unit time decision if there are enough tles to do so
+    fn settle_timing(&mut self) -> Result<Option<MorseTiming>, MorseErr> {
+        use MorseUnitTimeDecision::*;
+        let settled = self.unit_time.timing().is_some();
+        match self.unit_time {
+            EstimateToBeDetermined(config) => {
+                if let Some(unit_ms) = self.initial_estimate(config)? {
+                    self.unit_time = EstimateProvided(unit_ms);
+                }
+            }
+            EstimateTracked(config, None) => {
+                if let Some(unit_ms) = self.initial_estimate(config.derive)? {
+                    self.unit_time = EstimateTracked(config, Some(unit_ms));
+                }
+            }
+            TimingToBeDetermined(config) => {
+                if self.enough_tles(config.derive.guess_after_this_many_tles) {
+                    let v: Vec<_, TLES> = self.tles.iter().copied().collect();
+                    self.unit_time = TimingProvided(estimate_timing(&v[..], config)?.item);
+                }
+            }
+            EstimateProvided(_) | EstimateTracked(_, Some(_)) | TimingProvided(_) => (),
+        }
+        self.force_settle = false;
+        let timing = self.unit_time.timing();
+        if let (false, Some(timing)) = (settled, timing) {
+            self.observer.timing_locked(timing);
+        }
+        Ok(timing)
+    }
+
+    pub fn produce_chars_with_estimate<const D: usize>(
+        &mut self,
+        unit_ms: i64,
+    ) -> Result<Vec<char, D>, MorseErr> {
+        self.produce_chars_with_timing(MorseTiming::from_unit(unit_ms))
+    }
+
+    pub fn produce_chars_with_timing<const D: usize>(
+        &mut self,
+        timing: MorseTiming,
+    ) -> Result<Vec<char, D>, MorseErr> {
+        let r = self
+            .consume_tles(timing)
+            .and_then(|_| self.consume_morses());
+        r.map_err(|e| self.raise(e))
+    }
+
+    // Turns whatever samples are in into tles, and settles the timing if it can
+    fn ready_timing(&mut self) -> Result<Option<MorseTiming>, MorseErr> {
+        self.consume_samples()?;
+        self.check_idle()?;
+        self.settle_timing()
+    }
+
+    // The chars decoded so far. With set_erase_on_error_signal on, these can
+    // include ERASE, which takes back last_erase_len chars.
+    pub fn produce_chars<const D: usize>(&mut self) -> Result<Vec<char, D>, MorseErr> {
+        match self.ready_timing().map_err(|e| self.raise(e))? {
+            Some(timing) => self.produce_chars_with_timing(timing),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    // Like produce_chars, but each char goes to emit as it is decoded, so
+    // there's no Vec to run out of room in
+    pub fn produce_chars_with<F: FnMut(char)>(&mut self, mut emit: F) -> Result<(), MorseErr> {
+        match self.ready_timing().map_err(|e| self.raise(e))? {
+            Some(timing) => {
+                let r = self.consume_tles(timing).and_then(|_| {
+                    self.consume_morses_with(|decoded| {
+                        emit(decoded.c);
+                        Ok(())
+                    })
+                });
+                r.map_err(|e| self.raise(e))
             }
+            None => Ok(()),
+        }
+    }
+
+    // Like produce_chars, but with how confidently each char was decoded. An
+    // ERASE gets the error signal's timing, and how many chars it takes back.
+    pub fn produce_decoded<const D: usize>(&mut self) -> Result<Vec<DecodedChar, D>, MorseErr> {
+        match self.ready_timing().map_err(|e| self.raise(e))? {
+            Some(timing) => {
+                let r = self
+                    .consume_tles(timing)
+                    .and_then(|_| self.consume_morses_decoded());
+                r.map_err(|e| self.raise(e))
+            }
+            None => Ok(Vec::new()),
         }
     }
 }
@@ -343,50 +1377,48 @@ const MORSE_CANDIDATES: [MorseCandidate; 5] = [
     },
 ];
 
-pub type MorseSequenceSerialization = (u8, u8);
-pub type MorseKey = FnvIndexMap<MorseSequenceSerialization, char, U32>;
-
-pub fn construct_key() -> Result<MorseKey, ()> {
-    let elements = [
-        ((2u8, 0b00000010u8), 'a'),
-        ((4u8, 0b00000001u8), 'b'),
-        ((4u8, 0b00000101u8), 'c'),
-        ((3u8, 0b00000001u8), 'd'),
-        ((1u8, 0b00000000u8), 'e'),
-        ((4u8, 0b00000100u8), 'f'),
-        ((3u8, 0b00000011u8), 'g'),
-        ((4u8, 0b00000000u8), 'h'),
-        ((2u8, 0b00000000u8), 'i'),
-        ((4u8, 0b00001110u8), 'j'),
-        ((3u8, 0b00000101u8), 'k'),
-        ((4u8, 0b00000010u8), 'l'),
-        ((2u8, 0b00000011u8), 'm'),
-        ((2u8, 0b00000001u8), 'n'),
-        ((3u8, 0b00000111u8), 'o'),
-        ((4u8, 0b00000110u8), 'p'),
-        ((4u8, 0b00001011u8), 'q'),
-        ((3u8, 0b00000010u8), 'r'),
-        ((3u8, 0b00000000u8), 's'),
-        ((1u8, 0b00000001u8), 't'),
-        ((3u8, 0b00000100u8), 'u'),
-        ((4u8, 0b00001000u8), 'v'),
-        ((3u8, 0b00000110u8), 'w'),
-        ((4u8, 0b00001001u8), 'x'),
-        ((4u8, 0b00001101u8), 'y'),
-        ((4u8, 0b00000011u8), 'z'),
-    ];
-
-    let mut map = heapless::FnvIndexMap::new();
-    for ((count, rep), val) in elements.iter() {
-        map.insert((*count, *rep), *val).map_err(|_| ())?;
-    }
-    Ok(map)
+pub type MorseSequenceSerialization = (u8, u16);
+
+// Prosigns that share a code with ITU punctuation decode as that punctuation
+// (AR is '+', BT is '=', KN is '('). The rest are given private use chars
+// which produce_chars spells out as e.g. "<SK>".
+pub const PROSIGN_AS: char = '\u{E000}';
+pub const PROSIGN_KA: char = '\u{E001}';
+pub const PROSIGN_SK: char = '\u{E002}';
+pub const PROSIGN_SN: char = '\u{E003}';
+pub const PROSIGN_HH: char = '\u{E004}';
+pub const PROSIGN_SOS: char = '\u{E005}';
+
+// With set_erase_on_error_signal on, MorseConverter hands this back once in
+// place of the error signal (HH), and drops the space that follows the
+// signal. It takes back the word the signal came part way through, or else
+// the word before it and its space. DecodedChar::erases and last_erase_len
+// say how many chars that is.
+pub const ERASE: char = '\u{8}';
+
+const PROSIGN_NAMES: [(char, &str); 6] = [
+    (PROSIGN_AS, "<AS>"),
+    (PROSIGN_KA, "<KA>"),
+    (PROSIGN_SK, "<SK>"),
+    (PROSIGN_SN, "<SN>"),
+    (PROSIGN_HH, "<HH>"),
+    (PROSIGN_SOS, "<SOS>"),
+];
+
+pub fn prosign_name(c: char) -> Option<&'static str> {
+    PROSIGN_NAMES
+        .iter()
+        .find(|(p, _)| *p == c)
+        .map(|(_, name)| *name)
 }
 
-pub fn validate_morse_letter_tiny_spaces<C>(morse: Vec<Morse, C>) -> Result<Vec<Morse, C>, MorseErr>
-where
-    C: ArrayLength<Morse>,
-{
+pub fn construct_key() -> &'static MorseKey {
+    &LATIN_KEY
+}
+
+pub fn validate_morse_letter_tiny_spaces<const C: usize>(
+    morse: Vec<Morse, C>,
+) -> Result<Vec<Morse, C>, MorseErr> {
     let mut expect_tiny_spaces = 0;
     let mut count_tiny_spaces = 0;
 
@@ -415,9 +1447,9 @@ where
 pub fn serialize_morse(morse: &[Morse]) -> Result<MorseSequenceSerialization, MorseErr> {
     if morse.len() == 0 {
         Err(MorseErr::EmptyInput)
-    } else if morse.len() <= 8 {
-        let mut rep = 0u8;
-        let mut mask = 1u8;
+    } else if morse.len() <= MAX_CODE_LEN as usize {
+        let mut rep = 0u16;
+        let mut mask = 1u16;
         for m in morse {
             use Morse::*;
             let bit_set = match m {
@@ -441,6 +1473,50 @@ pub fn tle_to_best_morse(tle: &TimedLightEvent, unit_millis: Time) -> Result<Mor
     Ok(mc_to_morse(c.item)?)
 }
 
+pub fn track_unit_time(
+    tle: &TimedLightEvent,
+    scored: Scored<&MorseCandidate>,
+    unit_millis: Time,
+    config: TrackUnitTimeConfig,
+) -> Time {
+    let units = scored.item.units;
+    // Events further off than their own length (long pauses) say nothing about the unit
+    if scored.score > units * unit_millis || config.smoothing < 1 {
+        return unit_millis;
+    }
+    let implied = tle.duration / units;
+    let next = unit_millis + (implied - unit_millis) / config.smoothing;
+    next.max(config.derive.min_guess_ms)
+        .min(config.derive.max_guess_ms)
+}
+
+// How far the event is from being mistaken for its second best candidate,
+// from 100 (spot on) down to 0 (right between two candidates)
+pub fn classification_confidence(event: &TimedLightEvent, timing: MorseTiming) -> u8 {
+    let mut best: Option<Time> = None;
+    let mut second: Option<Time> = None;
+    let mut longest = 0;
+    for mc in MORSE_CANDIDATES.iter() {
+        let unit = timing.unit_for(mc);
+        if let Some(err) = calc_error(event, mc, unit) {
+            longest = longest.max(mc.units * unit);
+            match best {
+                Some(b) if err >= b => second = Some(second.map_or(err, |s| s.min(err))),
+                _ => {
+                    second = best;
+                    best = Some(err);
+                }
+            }
+        }
+    }
+    match (best, second) {
+        // Anything past the longest candidate can only be that candidate
+        _ if event.duration >= longest => 100,
+        (Some(b), Some(s)) if b + s > 0 => ((s - b) * 100 / (s + b)) as u8,
+        _ => 100,
+    }
+}
+
 pub fn calc_error(
     event: &TimedLightEvent,
     candidate: &MorseCandidate,
@@ -474,10 +1550,17 @@ fn poisoned_min<T>(
 pub fn best_error(
     event: &TimedLightEvent,
     unit_millis: Time,
+) -> Result<Scored<&MorseCandidate>, MorseErr> {
+    best_error_timed(event, MorseTiming::from_unit(unit_millis))
+}
+
+pub fn best_error_timed(
+    event: &TimedLightEvent,
+    timing: MorseTiming,
 ) -> Result<Scored<&MorseCandidate>, MorseErr> {
     let mut best = None;
     for mc in MORSE_CANDIDATES.iter() {
-        match (calc_error(event, mc, unit_millis), best) {
+        match (calc_error(event, mc, timing.unit_for(mc)), best) {
             (None, _) => continue,
             (Some(curr), None) => {
                 best = Some(Scored {
@@ -516,29 +1599,281 @@ pub fn score_possible_unit_millis(
     result
 }
 
+fn guess_millis(min_millis: Time, max_millis: Time) -> impl Iterator<Item = Time> {
+    let splits = 20;
+    (0..splits).map(move |ratio| {
+        let ratio = ratio as f32;
+        let ratio = ratio / (splits as f32);
+        let plus = (max_millis - min_millis) as f32 * ratio;
+        let plus = plus as Time;
+        min_millis + plus
+    })
+}
+
 pub fn estimate_unit_time(
     timings: &[TimedLightEvent],
     min_millis: Time,
     max_millis: Time,
 ) -> Result<Scored<Time>, MorseErr> {
-    let splits = 20;
-    // Iterate over possible unit times from 1 to 5000 ms
-    (0..splits)
+    cluster_unit_time(timings, min_millis, max_millis).map(|(scored, _)| scored)
+}
+
+// Also returns how many passes over the events it took, counting the scoring
+fn cluster_unit_time(
+    timings: &[TimedLightEvent],
+    min_millis: Time,
+    max_millis: Time,
+) -> Result<(Scored<Time>, u32), MorseErr> {
+    // From the marks, which are mostly dots and dashes, or failing that
+    // whatever there is
+    let (shortest, middle) = duration_spread(timings, |e| {
+        e.light_state == LightState::Light && e.duration >= min_millis
+    })
+    .or_else(|| duration_spread(timings, |e| e.duration >= min_millis))
+    .ok_or(MorseErr::EmptyInput)?;
+    // The shortest should be a dot unless it's a glitch, and a third of the
+    // middle one covers a stream that is mostly dashes, if there are marks
+    // that short
+    let seeds = [shortest, middle, middle / 3];
+    let seeds = match middle / 3 >= shortest {
+        true => &seeds[..],
+        false => &seeds[..2],
+    };
+    let (best, passes, agreed) = cluster_seeded(
+        timings,
+        seeds,
+        min_millis,
+        max_millis,
+        MorseTiming::from_unit,
+        |_| true,
+        |unit_millis| score_possible_unit_millis(unit_millis, timings),
+    )?;
+    // And one for finding the seeds
+    let passes = passes + 1;
+    // Seeds that settle on different units mean the events are spread
+    // unevenly enough to fool the clustering, so see if the grid does better
+    if agreed {
+        return Ok((best, passes));
+    }
+    let grid = grid_estimate_unit_time(timings, min_millis, max_millis)?;
+    let passes = passes + 20;
+    match grid.score < best.score {
+        true => Ok((grid, passes)),
+        false => Ok((best, passes)),
+    }
+}
+
+// The shortest of the events kept and the middle one of the first 64 kept,
+// which is plenty to find it from
+fn duration_spread<P>(timings: &[TimedLightEvent], keep: P) -> Option<(Time, Time)>
+where
+    P: Fn(&TimedLightEvent) -> bool,
+{
+    let shortest = timings
+        .iter()
+        .filter(|e| keep(e))
+        .map(|e| e.duration)
+        .min()?;
+    let mut first: Vec<Time, 64> = timings
+        .iter()
+        .filter(|e| keep(e))
+        .map(|e| e.duration)
+        .take(64)
+        .collect();
+    first.sort_unstable();
+    Some((shortest, first[first.len() / 2]))
+}
+
+// Clusters from each seed in turn and keeps the unit that scores best. Also
+// returns how many passes over the events that took, and whether every seed
+// led to the same unit.
+fn cluster_seeded<T, C, S>(
+    timings: &[TimedLightEvent],
+    seeds: &[Time],
+    min_millis: Time,
+    max_millis: Time,
+    timing: T,
+    counts: C,
+    score: S,
+) -> Result<(Scored<Time>, u32, bool), MorseErr>
+where
+    T: Fn(Time) -> MorseTiming,
+    C: Fn(&MorseCandidate) -> bool,
+    S: Fn(Time) -> Result<Scored<Time>, MorseErr>,
+{
+    let mut best: Option<Scored<Time>> = None;
+    let mut passes = 0;
+    let (mut lowest, mut highest) = (max_millis, min_millis);
+    for seed in seeds.iter() {
+        let (unit_millis, taken) =
+            cluster_fit(timings, *seed, min_millis, max_millis, &timing, &counts)?;
+        passes += taken;
+        lowest = lowest.min(unit_millis);
+        highest = highest.max(unit_millis);
+        let scored = match best {
+            Some(b) if b.item == unit_millis => continue,
+            _ => score(unit_millis)?,
+        };
+        passes += 1;
+        match best {
+            Some(b) if b.score <= scored.score => (),
+            _ => best = Some(scored),
+        }
+    }
+    // Rounding can leave seeds a millisecond or so apart on the same unit
+    let agreed = highest - lowest <= highest / 10;
+    Ok((best.ok_or(MorseErr::EmptyInput)?, passes, agreed))
+}
+
+// 1-D k-means with the cluster centres pinned at 1, 3 and 7 units: sort each
+// event into its nearest cluster, then refit the unit to them. timing makes
+// the unit being fit into a timing, and only events classified as a
+// candidate that counts go toward it, so the same loop fits a lone unit, the
+// element unit to the marks, or the spacing unit to the gaps. Also returns
+// how many passes over the events it took.
+fn cluster_fit<T, C>(
+    timings: &[TimedLightEvent],
+    seed: Time,
+    min_millis: Time,
+    max_millis: Time,
+    timing: T,
+    counts: C,
+) -> Result<(Time, u32), MorseErr>
+where
+    T: Fn(Time) -> MorseTiming,
+    C: Fn(&MorseCandidate) -> bool,
+{
+    let mut unit_millis = seed.max(min_millis).min(max_millis);
+    let mut passes = 0;
+    for _ in 0..10 {
+        let mut duration_sum = 0;
+        let mut units_sum = 0;
+        for event in timings {
+            let best = best_error_timed(event, timing(unit_millis))?;
+            // Events further off than their own length (long pauses) say nothing about the unit
+            if !counts(best.item) || best.score > best.item.units * unit_millis {
+                continue;
+            }
+            duration_sum += event.duration;
+            units_sum += best.item.units;
+        }
+        passes += 1;
+        let next = match duration_sum.checked_div(units_sum) {
+            Some(next) => next.max(min_millis).min(max_millis),
+            None => break,
+        };
+        if next == unit_millis {
+            break;
+        }
+        unit_millis = next;
+    }
+    Ok((unit_millis, passes))
+}
+
+// The original estimator: tries 20 evenly spaced unit times between min and max
+pub fn grid_estimate_unit_time(
+    timings: &[TimedLightEvent],
+    min_millis: Time,
+    max_millis: Time,
+) -> Result<Scored<Time>, MorseErr> {
+    // Iterate over possible unit times between min and max
+    guess_millis(min_millis, max_millis)
         // For each time, score it by summing the scores of the best candidate for each event
-        .map(|ratio| {
-            let ratio = ratio as f32;
-            let ratio = ratio / (splits as f32);
-            let plus = (max_millis - min_millis) as f32 * ratio;
-            let plus = plus as Time;
-            score_possible_unit_millis(min_millis + plus, timings)
-            // score_possible_unit_millis(ratio, timings)
-        })
+        .map(|unit_millis| score_possible_unit_millis(unit_millis, timings))
         // Converge on the minimum scoring unit time
         .fold(None, poisoned_min)
         // Ignore possible errors and pull out the best scoring unit time
         .unwrap_or(Err(MorseErr::EmptyInput))
 }
 
+pub fn score_possible_timing(
+    timing: MorseTiming,
+    timings: &[TimedLightEvent],
+    light_state: LightState,
+) -> Result<Scored<MorseTiming>, MorseErr> {
+    let mut sum = 0;
+    for event in timings.iter().filter(|e| e.light_state == light_state) {
+        sum += best_error_timed(event, timing)?.score;
+    }
+    Ok(Scored {
+        item: timing,
+        score: sum,
+    })
+}
+
+pub fn estimate_timing(
+    timings: &[TimedLightEvent],
+    config: DeriveTimingConfig,
+) -> Result<Scored<MorseTiming>, MorseErr> {
+    let DeriveUnitTimeConfig {
+        min_guess_ms: min,
+        max_guess_ms: max,
+        ..
+    } = config.derive;
+
+    // Marks only ever use the element unit, so pin that down first
+    let (shortest, middle) = duration_spread(timings, |e| {
+        e.light_state == LightState::Light && e.duration >= min
+    })
+    .ok_or(MorseErr::EmptyInput)?;
+    let (marks, _, _) = cluster_seeded(
+        timings,
+        &[shortest, middle, middle / 3],
+        min,
+        max,
+        MorseTiming::from_unit,
+        |mc| mc.light_state == LightState::Light,
+        |unit_ms| {
+            let scored =
+                score_possible_timing(MorseTiming::from_unit(unit_ms), timings, LightState::Light)?;
+            Ok(Scored {
+                item: unit_ms,
+                score: scored.score,
+            })
+        },
+    )?;
+    let unit_ms = marks.item;
+
+    // Then fit the letter and word gaps with a spacing unit at least that
+    // long, starting from the shortest and middle gaps long enough to be
+    // either, taken as letter gaps
+    let max_spacing = config.max_spacing_ms.max(unit_ms);
+    let timing = |spacing_ms| MorseTiming {
+        unit_ms,
+        spacing_ms,
+    };
+    let seeds = match duration_spread(timings, |e| {
+        e.light_state == LightState::Dark && e.duration > 2 * unit_ms
+    }) {
+        Some((shortest, middle)) => [unit_ms, shortest / 3, middle / 3],
+        None => [unit_ms; 3],
+    };
+    let (gaps, _, _) = cluster_seeded(
+        timings,
+        &seeds,
+        unit_ms,
+        max_spacing,
+        timing,
+        |mc| mc.light_state == LightState::Dark && mc.units > 1,
+        |spacing_ms| {
+            let scored = score_possible_timing(timing(spacing_ms), timings, LightState::Dark)?;
+            Ok(Scored {
+                item: spacing_ms,
+                score: scored.score,
+            })
+        },
+    )?;
+    let gaps = Scored {
+        item: timing(gaps.item),
+        score: gaps.score,
+    };
+
+    Ok(Scored {
+        item: gaps.item,
+        score: marks.score + gaps.score,
+    })
+}
+
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum CalcDigitalCutoffsErrs {
     TooBig(core::num::TryFromIntError),
@@ -607,128 +1942,99 @@ pub enum ConvertErrs {
     TooSmallOutgoingCapacity,
 }
 
-pub fn intensities_to_tles<C>(
-    intensities: &mut Consumer<SampledLightIntensity, C, usize>,
+pub fn intensities_to_tles<const C: usize, const T: usize>(
+    intensities: &mut Consumer<SampledLightIntensity, C>,
     init: (Time, LightState),
     cuts: IntensityCutoffs,
     dark_push_time: Option<Time>,
-) -> Result<ConsumeSamplesInfo<C>, ConvertErrs>
+) -> Result<ConsumeSamplesInfo<T>, ConvertErrs> {
+    intensities_to_tles_with(intensities, init, dark_push_time, |_| cuts)
+}
+
+// Like intensities_to_tles, but asks cuts_for which cutoffs to use as each
+// sample comes in
+pub fn intensities_to_tles_with<const C: usize, const T: usize, F>(
+    intensities: &mut Consumer<SampledLightIntensity, C>,
+    init: (Time, LightState),
+    dark_push_time: Option<Time>,
+    mut cuts_for: F,
+) -> Result<ConsumeSamplesInfo<T>, ConvertErrs>
 where
-    C: heapless::ArrayLength<SampledLightIntensity> + ArrayLength<TimedLightEvent>,
+    F: FnMut(LightIntensity) -> IntensityCutoffs,
 {
     use ConvertErrs::*;
-    use LightState::*;
-    let (mut start_time, mut curr_light_state) = init;
+    let mut state = init;
 
-    let mut out_vec: Vec<_, C> = Vec::new();
+    let mut out_vec: Vec<_, T> = Vec::new();
 
     while intensities.ready() {
-        let it: Option<SampledLightIntensity> = intensities.dequeue();
-        let SampledLightIntensity {
-            sample_time: time,
-            intensity: light,
-        } = it.ok_or(BadQueueCode)?;
-
-        let mut next_light_state = match (curr_light_state, light) {
-            (Dark, light) if light > cuts.high => Some(Light),
-            (Light, light) if light < cuts.low => Some(Dark),
-            _ => None,
-        };
-
-        match (next_light_state, dark_push_time) {
-            (None, Some(dark_push_time)) => {
-                if time - start_time > dark_push_time && light < cuts.low {
-                    next_light_state = Some(Dark);
-                }
-            }
-            _ => (),
-        };
-
-        match next_light_state {
-            Some(next_light_state) => {
-                let tle = TimedLightEvent {
-                    light_state: curr_light_state,
-                    duration: time - start_time,
-                };
-
-                out_vec.push(tle).map_err(|_| TooSmallOutgoingCapacity)?;
-                curr_light_state = next_light_state;
-                start_time = time;
-            }
-            _ => (),
-        };
+        let sli = intensities.dequeue().ok_or(BadQueueCode)?;
+        let cuts = cuts_for(sli.intensity);
+        if let Some(tle) = next_tle(&mut state, sli, cuts, dark_push_time) {
+            out_vec.push(tle).map_err(|_| TooSmallOutgoingCapacity)?;
+        }
     }
     Ok(ConsumeSamplesInfo {
         tles: out_vec,
-        state: (start_time, curr_light_state),
+        state,
     })
 }
 
-pub fn definitive_consume_morses_produce_letter<C>(
-    incoming: &mut Consumer<Morse, C, usize>,
-    mut hold_word: Queue<Morse, C, usize>,
-    mkey: &MorseKey,
-) -> Result<(Option<char>, Queue<Morse, C, usize>), MorseErr>
-where
-    C: ArrayLength<Morse>,
-{
-    loop {
-        match private_consume_morses_produce_letter(incoming, hold_word, mkey) {
-            Err(e) => break Err(e),
-            Ok((None, q)) if incoming.ready() => hold_word = q,
-            Ok((None, q)) => break Ok((None, q)),
-            Ok((Some(c), q)) => break Ok((Some(c), q)),
+// Moves state on by one sample, returning the event the sample ends if it
+// ends one
+fn next_tle(
+    state: &mut (Time, LightState),
+    sli: SampledLightIntensity,
+    cuts: IntensityCutoffs,
+    dark_push_time: Option<Time>,
+) -> Option<TimedLightEvent> {
+    use LightState::*;
+    let (start_time, curr_light_state) = *state;
+    let SampledLightIntensity {
+        sample_time: time,
+        intensity: light,
+    } = sli;
+
+    let mut next_light_state = match (curr_light_state, light) {
+        (Dark, light) if light > cuts.high => Some(Light),
+        (Light, light) if light < cuts.low => Some(Dark),
+        _ => None,
+    };
+
+    match (next_light_state, dark_push_time) {
+        (None, Some(dark_push_time)) => {
+            if time - start_time > dark_push_time && light < cuts.low {
+                next_light_state = Some(Dark);
+            }
         }
-    }
+        _ => (),
+    };
+
+    let next_light_state = next_light_state?;
+    *state = (time, next_light_state);
+    Some(TimedLightEvent {
+        light_state: curr_light_state,
+        duration: time - start_time,
+    })
 }
 
-fn private_consume_morses_produce_letter<C>(
-    incoming: &mut Consumer<Morse, C, usize>,
-    mut hold_word: Queue<Morse, C, usize>,
+// Takes morses until a letter or word space is done, or there are none left
+pub fn definitive_consume_morses_produce_letter<const C: usize>(
+    incoming: &mut Consumer<Morse, C>,
+    letter: &mut LetterAssembler,
     mkey: &MorseKey,
-) -> Result<(Option<char>, Queue<Morse, C, usize>), MorseErr>
-where
-    C: ArrayLength<Morse>,
-{
-    use Morse::*;
-    if hold_word.peek() == Some(&WordSpace) {
-        hold_word.dequeue();
-        Ok((Some(' '), hold_word))
-    } else {
-        let mut next_queue = None;
-
-        // We set next_queue based on the break condition
-        while incoming.ready() && next_queue.is_none() {
-            let morse = incoming.dequeue().ok_or(MorseErr::QueueBug)?;
-
-            if morse == LetterSpace {
-                next_queue = Some(Queue::new());
-            } else if morse == WordSpace {
-                let mut q = Queue::new();
-                q.enqueue(WordSpace).map_err(|_| MorseErr::InputTooLarge)?;
-                next_queue = Some(q);
-            } else {
-                hold_word
-                    .enqueue(morse)
-                    .map_err(|_| MorseErr::InputTooLarge)?;
-            }
+) -> Result<Option<char>, MorseErr> {
+    loop {
+        if letter.take_space() {
+            return Ok(Some(' '));
         }
-        match next_queue {
-            Some(next_queue) => {
-                if hold_word.len() == 0 {
-                    Ok((None, next_queue))
-                } else if hold_word.len() <= 8 {
-                    let v: Vec<Morse, U8> = hold_word.iter().map(|m| *m).collect();
-                    let v = validate_morse_letter_tiny_spaces(v)?;
-
-                    let ser = serialize_morse(&v[..])?;
-                    let c = mkey.get(&ser).ok_or(MorseErr::UnknownChar(ser))?;
-                    Ok((Some(*c), next_queue))
-                } else {
-                    Err(MorseErr::InputTooLarge)
+        match incoming.dequeue() {
+            Some(morse) => {
+                if let Some(c) = letter.push(morse, mkey)? {
+                    return Ok(Some(c));
                 }
             }
-            None => Ok((None, hold_word)),
+            None => return Ok(None),
         }
     }
 }
@@ -736,7 +2042,6 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use heapless::consts::*;
     use heapless::Vec;
     extern crate std;
     use std::{cmp::min, println};
@@ -810,10 +2115,10 @@ mod tests {
         assert_eq!(0, best_error_helper(Light, 75, 25));
     }
 
-    fn helper_fill_events_slice<T>(durations: &[i64], vec: &mut Vec<TimedLightEvent, T>)
-    where
-        T: heapless::ArrayLength<TimedLightEvent>,
-    {
+    fn helper_fill_events_slice<const T: usize>(
+        durations: &[i64],
+        vec: &mut Vec<TimedLightEvent, T>,
+    ) {
         for (i, duration) in durations.iter().enumerate() {
             vec.push(TimedLightEvent {
                 light_state: {
@@ -837,7 +2142,7 @@ mod tests {
             300, 100, 300, 300, 300, 100, 300, 100, 300, 300, 100, 100, 100, 100, 300, 100, 100,
             700,
         ];
-        let mut timed_light_events: Vec<TimedLightEvent, U128> = Vec::new();
+        let mut timed_light_events: Vec<TimedLightEvent, 128> = Vec::new();
         helper_fill_events_slice(&test_durations, &mut timed_light_events);
         assert_eq!(
             Scored {
@@ -848,6 +2153,90 @@ mod tests {
         );
     }
 
+    // Captures hold one intensity per line, taken a millisecond apart. The
+    // runs at either end are idle time rather than Morse, so they are dropped.
+    fn helper_capture_tles(capture: &str) -> std::vec::Vec<TimedLightEvent> {
+        let samples: std::vec::Vec<_> = capture
+            .lines()
+            .filter_map(|l| l.trim().parse().ok())
+            .enumerate()
+            .map(|(i, intensity)| SampledLightIntensity {
+                sample_time: i as Time,
+                intensity,
+            })
+            .collect();
+        let cutoffs = calc_digital_cutoffs(&samples).unwrap();
+        let threshold = (cutoffs.low + cutoffs.high) / 2;
+
+        let mut tles = std::vec::Vec::new();
+        let state_of = |s: &SampledLightIntensity| match s.intensity > threshold {
+            true => LightState::Light,
+            false => LightState::Dark,
+        };
+        let mut start = &samples[0];
+        for s in samples.iter() {
+            if state_of(s) != state_of(start) {
+                tles.push(TimedLightEvent {
+                    light_state: state_of(start),
+                    duration: s.sample_time - start.sample_time,
+                });
+                start = s;
+            }
+        }
+        tles.remove(0);
+        tles
+    }
+
+    #[test]
+    fn test_cluster_vs_grid_captures() {
+        for (capture, unit_ms) in [
+            (include_str!("../fastcar.txt"), 29),
+            (include_str!("../slow.txt"), 29),
+        ]
+        .iter()
+        {
+            let tles = helper_capture_tles(capture);
+            let grid = grid_estimate_unit_time(&tles, 10, 200).unwrap();
+            let (cluster, passes) = cluster_unit_time(&tles, 10, 200).unwrap();
+            assert_eq!(*unit_ms, cluster.item);
+            assert!(cluster.score <= grid.score);
+            // The grid scores each of its 20 guesses, a pass over the events apiece
+            assert!(passes < 20);
+
+            // Over a wide range the grid steps right past the unit
+            let grid = grid_estimate_unit_time(&tles, 10, 1000).unwrap();
+            let (wide, _) = cluster_unit_time(&tles, 10, 1000).unwrap();
+            assert_eq!(cluster, wide);
+            assert!(wide.score < grid.score);
+        }
+    }
+
+    #[test]
+    fn test_cluster_past_glitch() {
+        let key = construct_key();
+        let mut tles: Vec<TimedLightEvent, 64> = Vec::new();
+        tles.push(TimedLightEvent {
+            light_state: LightState::Light,
+            duration: 25,
+        })
+        .unwrap();
+        tles.push(morse_to_tle(Morse::WordSpace, 60)).unwrap();
+        for pair in MorseEncoder::new("paris ", key).timed(60) {
+            tles.push(pair.unwrap().1).unwrap();
+        }
+        // The glitch is the shortest mark, and clustering from it alone
+        // settles on a third of the unit
+        let (from_glitch, _) =
+            cluster_fit(&tles, 25, 10, 200, MorseTiming::from_unit, |_| true).unwrap();
+        assert_eq!(21, from_glitch);
+
+        // The glitch still counts as a dot, so it pulls the unit in a little
+        let (cluster, _) = cluster_unit_time(&tles, 10, 200).unwrap();
+        assert_eq!(59, cluster.item);
+        let grid = grid_estimate_unit_time(&tles, 10, 200).unwrap();
+        assert!(cluster.score < grid.score);
+    }
+
     #[test]
     fn test_queue_convert() {
         let my_intensities = [
@@ -867,7 +2256,7 @@ mod tests {
 
         use heapless::spsc::Queue;
 
-        let mut sample_queue: Queue<_, U64, _> = Queue::new();
+        let mut sample_queue: Queue<_, 64> = Queue::new();
         let results = my_intensities
             .iter()
             .map(|(i, t)| SampledLightIntensity {
@@ -880,7 +2269,7 @@ mod tests {
 
         let (mut consumer, mut producer) = sample_queue.split();
 
-        let popresult = intensities_to_tles(
+        let popresult: ConsumeSamplesInfo<16> = intensities_to_tles(
             &mut producer,
             (0, LightState::Dark),
             IntensityCutoffs {
@@ -891,7 +2280,7 @@ mod tests {
         )
         .unwrap();
 
-        let rmorses: Result<Vec<_, U64>, _> = popresult
+        let rmorses: Result<Vec<_, 64>, _> = popresult
             .tles
             .iter()
             .map(|t| tle_to_best_morse(t, 20))
@@ -919,7 +2308,7 @@ mod tests {
                 .unwrap();
         }
 
-        let popresult = intensities_to_tles(
+        let popresult: Result<ConsumeSamplesInfo<16>, _> = intensities_to_tles(
             &mut producer,
             popresult.state,
             IntensityCutoffs {
@@ -929,7 +2318,7 @@ mod tests {
             None,
         );
 
-        let rmorses: Result<Vec<_, U64>, _> = popresult
+        let rmorses: Result<Vec<_, 64>, _> = popresult
             .unwrap()
             .tles
             .iter()
@@ -964,18 +2353,56 @@ mod tests {
     fn test_lookup() {
         use Morse::*;
         let arr = [Dash, Dot, Dot, Dot];
-        let key = construct_key().unwrap();
+        let key = construct_key();
         let ser = serialize_morse(&arr).unwrap();
         assert_eq!(Some(&'b'), key.get(&ser));
     }
 
+    #[test]
+    fn test_lookup_itu() {
+        use Morse::*;
+        let key = construct_key();
+        let ser = serialize_morse(&[Dot, Dot, Dash, Dash, Dot, Dot]).unwrap();
+        assert_eq!(Some(&'?'), key.get(&ser));
+        let ser = serialize_morse(&[Dash, Dash, Dash, Dash, Dash]).unwrap();
+        assert_eq!(Some(&'0'), key.get(&ser));
+        let ser = serialize_morse(&[Dot, Dot, Dot, Dash, Dot, Dash]).unwrap();
+        assert_eq!(Some(&PROSIGN_SK), key.get(&ser));
+        assert_eq!(Some("<SK>"), prosign_name(PROSIGN_SK));
+    }
+
+    #[test]
+    fn test_consume_every_key_entry() {
+        use Morse::*;
+        let key = construct_key();
+
+        for ((count, rep), c) in key.iter() {
+            let mut morse_queue: Queue<_, 64> = Queue::new();
+            let (mut consumer, mut producer) = morse_queue.split();
+            for i in 0..count {
+                if i > 0 {
+                    consumer.enqueue(TinySpace).unwrap();
+                }
+                let dash = rep & (1 << i) != 0;
+                consumer.enqueue(if dash { Dash } else { Dot }).unwrap();
+            }
+            consumer.enqueue(LetterSpace).unwrap();
+
+            let mut letter = LetterAssembler::new();
+            let char =
+                definitive_consume_morses_produce_letter(&mut producer, &mut letter, key).unwrap();
+            assert_eq!(Some(c), char);
+            assert!(letter.is_empty());
+        }
+    }
+
     #[test]
     fn test_consume() {
         use Morse::*;
 
-        let key = construct_key().unwrap();
+        let key = construct_key();
 
-        let mut morse_queue: Queue<_, U64, _> = Queue::new();
+        let mut morse_queue: Queue<_, 64> = Queue::new();
         let (mut consumer, mut producer) = morse_queue.split();
 
         consumer.enqueue(Dot).unwrap();
@@ -983,30 +2410,30 @@ mod tests {
         consumer.enqueue(Dot).unwrap();
         consumer.enqueue(TinySpace).unwrap();
 
-        let q = Queue::new();
+        let mut letter = LetterAssembler::new();
 
-        let (char, q) = private_consume_morses_produce_letter(&mut producer, q, &key).unwrap();
-        assert_eq!(None, char);
+        let char = definitive_consume_morses_produce_letter(&mut producer, &mut letter, key);
+        assert_eq!(Ok(None), char);
 
         consumer.enqueue(Dot).unwrap();
 
-        let (char, q) = private_consume_morses_produce_letter(&mut producer, q, &key).unwrap();
-        assert_eq!(None, char);
+        let char = definitive_consume_morses_produce_letter(&mut producer, &mut letter, key);
+        assert_eq!(Ok(None), char);
 
         consumer.enqueue(LetterSpace).unwrap();
 
-        let (char, q) = private_consume_morses_produce_letter(&mut producer, q, &key).unwrap();
-        assert_eq!(Some('s'), char);
-        assert!(q.is_empty());
+        let char = definitive_consume_morses_produce_letter(&mut producer, &mut letter, key);
+        assert_eq!(Ok(Some('s')), char);
+        assert!(letter.is_empty());
     }
 
     #[test]
     fn test_consume2() {
         use Morse::*;
 
-        let key = construct_key().unwrap();
+        let key = construct_key();
 
-        let mut morse_queue: Queue<_, U64, _> = Queue::new();
+        let mut morse_queue: Queue<_, 64> = Queue::new();
         let (mut consumer, mut producer) = morse_queue.split();
 
         consumer.enqueue(Dot).unwrap();
@@ -1038,14 +2465,13 @@ mod tests {
         consumer.enqueue(LetterSpace).unwrap();
         consumer.enqueue(LetterSpace).unwrap();
 
-        let mut cvec: Vec<char, U64> = Vec::new();
-        let mut q = Queue::new();
+        let mut cvec: Vec<char, 64> = Vec::new();
+        let mut letter = LetterAssembler::new();
 
         loop {
-            let (char, newqueue) =
-                definitive_consume_morses_produce_letter(&mut producer, q, &key).unwrap();
-            q = newqueue;
-            println!("{:?}", q);
+            let char =
+                definitive_consume_morses_produce_letter(&mut producer, &mut letter, key).unwrap();
+            println!("{:?}", letter);
             match char {
                 Some(c) => cvec.push(c).unwrap(),
                 None => break,
@@ -1075,7 +2501,7 @@ mod tests {
             (100, 800),
         ];
 
-        let mut converter: MorseConverter<U64> = MorseConverter::new(
+        let mut converter: MorseConverter<64, 64, 64> = MorseConverter::new(
             0,
             MorseUnitTimeDecision::EstimateProvided(20),
             IntensityCutoffs {
@@ -1083,8 +2509,7 @@ mod tests {
                 high: 800,
             },
             Some(200),
-        )
-        .unwrap();
+        );
 
         for (light, time) in my_intensities.iter() {
             converter
@@ -1095,7 +2520,7 @@ mod tests {
                 .unwrap();
         }
 
-        let vec: Vec<_, U32> = converter.produce_chars().unwrap();
+        let vec: Vec<_, 32> = converter.produce_chars().unwrap();
 
         assert_eq!(&['b', ' ', 'e', ' '], &vec[..]);
     }
@@ -1144,7 +2569,7 @@ mod tests {
             (100, 1640),
         ];
 
-        let mut converter: MorseManager<U64, U64> = MorseManager::new(
+        let mut converter: MorseManager<128, 64, 64, 64> = MorseManager::new(
             500,
             MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
                 guess_after_this_many_tles: 7,
@@ -1162,10 +2587,882 @@ mod tests {
                 .unwrap();
         }
 
-        let vec: Vec<_, U32> = converter.produce_chars().unwrap();
+        let vec: Vec<_, 32> = converter.produce_chars().unwrap();
 
         assert_eq!(&['b', ' ', 'e', 'd', 'o', 'g', ' '], &vec[..]);
     }
+
+    #[test]
+    fn test_ram_bytes() {
+        type Even = MorseConverter<'static, 128, 128, 128>;
+        type FewMorses = MorseConverter<'static, 128, 128, 32>;
+        // Checked at compile time, as firmware would against its RAM
+        const _: () = assert!(FewMorses::RAM_BYTES < Even::RAM_BYTES);
+
+        assert_eq!(Even::SAMPLES_BYTES, FewMorses::SAMPLES_BYTES);
+        assert_eq!(Even::TLES_BYTES, FewMorses::TLES_BYTES);
+        assert_eq!(
+            Even::RAM_BYTES - FewMorses::RAM_BYTES,
+            Even::MORSES_BYTES - FewMorses::MORSES_BYTES
+        );
+        const _: () =
+            assert!(Even::RAM_BYTES > Even::SAMPLES_BYTES + Even::TLES_BYTES + Even::MORSES_BYTES);
+        const _: () = assert!(
+            MorseManager::<'static, 128, 128, 128, 64>::RAM_BYTES
+                > Even::RAM_BYTES + 64 * size_of::<SampledLightIntensity>()
+        );
+    }
+
+    fn helper_encode_samples<const T: usize>(
+        text: &str,
+        key: &MorseKey,
+        timing: MorseTiming,
+        time: &mut Time,
+        vec: &mut Vec<SampledLightIntensity, T>,
+    ) {
+        // One sample at the start of each event
+        for pair in MorseEncoder::new(text, key).timed_with(timing) {
+            let (_, tle) = pair.unwrap();
+            let intensity = match tle.light_state {
+                LightState::Light => 900,
+                LightState::Dark => 100,
+            };
+            vec.push(SampledLightIntensity {
+                intensity,
+                sample_time: *time,
+            })
+            .unwrap();
+            *time += tle.duration;
+        }
+    }
+
+    fn helper_close_samples<const T: usize>(time: Time, vec: &mut Vec<SampledLightIntensity, T>) {
+        vec.push(SampledLightIntensity {
+            intensity: 900,
+            sample_time: time,
+        })
+        .unwrap();
+    }
+
+    // Text as samples from 60ms on, closed off by the start of another mark
+    pub(crate) fn helper_text_samples(
+        text: &str,
+        key: &MorseKey,
+        timing: MorseTiming,
+    ) -> Vec<SampledLightIntensity, 256> {
+        let mut time = 60;
+        let mut samples = Vec::new();
+        helper_encode_samples(text, key, timing, &mut time, &mut samples);
+        helper_close_samples(time, &mut samples);
+        samples
+    }
+
+    // A MorseManager or a MorseConverter, for helper_decode_text
+    pub(crate) trait SampleDecoder {
+        fn add(&mut self, sli: SampledLightIntensity) -> Result<(), MorseErr>;
+        fn produce(&mut self) -> Result<Vec<char, 32>, MorseErr>;
+    }
+
+    impl<const S: usize, const T: usize, const M: usize, const W: usize, O> SampleDecoder
+        for MorseManager<'_, S, T, M, W, O>
+    where
+        O: MorseObserver,
+    {
+        fn add(&mut self, sli: SampledLightIntensity) -> Result<(), MorseErr> {
+            self.add_sample(sli)
+        }
+        fn produce(&mut self) -> Result<Vec<char, 32>, MorseErr> {
+            self.produce_chars()
+        }
+    }
+
+    impl<const S: usize, const T: usize, const M: usize, O> SampleDecoder
+        for MorseConverter<'_, S, T, M, O>
+    where
+        O: MorseObserver,
+    {
+        fn add(&mut self, sli: SampledLightIntensity) -> Result<(), MorseErr> {
+            self.add_sample(sli)
+        }
+        fn produce(&mut self) -> Result<Vec<char, 32>, MorseErr> {
+            self.produce_chars()
+        }
+    }
+
+    // Sends text through the decoder and produces what came out of it
+    pub(crate) fn helper_decode_text<D: SampleDecoder>(
+        decoder: &mut D,
+        text: &str,
+        key: &MorseKey,
+        timing: MorseTiming,
+    ) -> Result<Vec<char, 32>, MorseErr> {
+        for sli in helper_text_samples(text, key, timing) {
+            decoder.add(sli)?;
+        }
+        decoder.produce()
+    }
+
+    fn helper_stream_decode<const T: usize>(
+        samples: Vec<SampledLightIntensity, T>,
+        unit_time: MorseUnitTimeDecision,
+    ) -> (Vec<char, 32>, Option<MorseTiming>) {
+        let cuts = IntensityCutoffs {
+            low: 200,
+            high: 800,
+        };
+        let mut converter: MorseConverter<256, 256, 256> =
+            MorseConverter::new(0, unit_time, cuts, None);
+        // Decode as we go, like the firmware does
+        let mut chars = Vec::new();
+        for sli in samples {
+            converter.add_sample(sli).unwrap();
+            match converter.produce_chars::<8>() {
+                Ok(new_chars) => chars.extend_from_slice(&new_chars).unwrap(),
+                Err(_) => break,
+            }
+        }
+        (chars, converter.timing_estimate())
+    }
+
+    // Decodes as we go with the error signal erasing, into no more than 8
+    // chars at a time, and takes back what each ERASE says to
+    fn helper_erased_decode(text: &str, send_key: &MorseKey) -> Vec<char, 32> {
+        let samples = helper_text_samples(text, send_key, MorseTiming::from_unit(20));
+        let cuts = IntensityCutoffs {
+            low: 200,
+            high: 800,
+        };
+        let unit_time = MorseUnitTimeDecision::EstimateProvided(20);
+        let mut converter: MorseConverter<256, 256, 256> =
+            MorseConverter::new(0, unit_time, cuts, None);
+        converter.set_erase_on_error_signal(true);
+        converter.set_replacement(Some('?'));
+        let mut shown = Vec::new();
+        for sli in samples {
+            converter.add_sample(sli).unwrap();
+            let chars: Vec<char, 8> = converter.produce_chars().unwrap();
+            for c in chars {
+                match c {
+                    ERASE => {
+                        for _ in 0..converter.last_erase_len() {
+                            shown.pop().unwrap();
+                        }
+                    }
+                    c => shown.push(c).unwrap(),
+                }
+            }
+        }
+        shown
+    }
+
+    #[test]
+    fn test_error_signal() {
+        let key = construct_key();
+        // After a word, and part way through one
+        let expected: Vec<char, 32> = "the quick <SOS> ".chars().collect();
+        assert_eq!(
+            expected,
+            helper_erased_decode("the quick <HH> quick <SOS> ", key)
+        );
+        let expected: Vec<char, 32> = "the quick ".chars().collect();
+        assert_eq!(expected, helper_erased_decode("the qui<HH> quick ", key));
+
+        // A word longer than a produce_chars call can hand back
+        let expected: Vec<char, 32> = "a ok ".chars().collect();
+        assert_eq!(
+            expected,
+            helper_erased_decode("a abcdefghijklmnop <HH> ok ", key)
+        );
+
+        // Letters that failed to decode aren't a word to take back. Six
+        // dashes isn't in the Latin key, and eight dots are the error signal.
+        let send_key =
+            construct_custom_key(&[((1, 0), 'e'), ((6, 0b00111111), '#'), ((8, 0), 'h')]).unwrap();
+        let expected: Vec<char, 32> = "ee ?? e ".chars().collect();
+        assert_eq!(expected, helper_erased_decode("ee ## h e ", &send_key));
+
+        // Spelled out by default, with the space after it kept
+        let mut manager: MorseManager<128, 64, 64, 64> =
+            MorseManager::new(500, MorseUnitTimeDecision::EstimateProvided(20));
+        let text = "the <HH> tea ";
+        let chars = helper_decode_text(&mut manager, text, key, MorseTiming::from_unit(20));
+        let expected: Vec<char, 32> = text.chars().collect();
+        assert_eq!(Ok(expected), chars);
+    }
+
+    fn helper_speed_change(unit_time: MorseUnitTimeDecision) -> (Vec<char, 32>, Option<Time>) {
+        let key = construct_key();
+        let mut time = 60;
+        let mut samples: Vec<_, 256> = Vec::new();
+        for unit_ms in [20, 30, 40].iter() {
+            let timing = MorseTiming::from_unit(*unit_ms);
+            helper_encode_samples("paris ", key, timing, &mut time, &mut samples);
+        }
+        helper_close_samples(time, &mut samples);
+
+        let (chars, timing) = helper_stream_decode(samples, unit_time);
+        (chars, timing.map(|t| t.unit_ms))
+    }
+
+    #[test]
+    fn test_tracked_unit_time() {
+        let derive = DeriveUnitTimeConfig {
+            guess_after_this_many_tles: 7,
+            max_guess_ms: 60,
+            min_guess_ms: 10,
+        };
+        let tracked = MorseUnitTimeDecision::EstimateTracked(
+            TrackUnitTimeConfig {
+                derive,
+                smoothing: 4,
+            },
+            None,
+        );
+
+        let (chars, estimate) = helper_speed_change(tracked);
+        let expected: Vec<char, 32> = "paris paris paris ".chars().collect();
+        assert_eq!(expected, chars);
+        let estimate = estimate.unwrap();
+        assert!(estimate > 35 && estimate < 45);
+
+        let fixed = MorseUnitTimeDecision::EstimateToBeDetermined(derive);
+        let (chars, estimate) = helper_speed_change(fixed);
+        assert_ne!(expected, chars);
+        assert_eq!(Some(20), estimate);
+    }
+
+    // A sample every 5ms, with the light dimming after the first word
+    fn helper_dimming_decode(tracking: Option<CutoffTrackerConfig>) -> Vec<char, 32> {
+        let key = construct_key();
+        let mut converter: MorseConverter<512, 512, 512> = MorseConverter::new(
+            0,
+            MorseUnitTimeDecision::EstimateProvided(20),
+            IntensityCutoffs {
+                low: 300,
+                high: 700,
+            },
+            None,
+        );
+        converter.set_cutoff_tracking(tracking);
+
+        let mut time = 0;
+        let mut out = Vec::new();
+        for (text, light, dark) in [("paris ", 900, 100), ("paris paris ", 450, 50)].iter() {
+            for pair in MorseEncoder::new(text, key).timed(20) {
+                let (_, tle) = pair.unwrap();
+                let intensity = match tle.light_state {
+                    LightState::Light => *light,
+                    LightState::Dark => *dark,
+                };
+                let end = time + tle.duration;
+                while time < end {
+                    converter
+                        .add_sample(SampledLightIntensity {
+                            intensity,
+                            sample_time: time,
+                        })
+                        .unwrap();
+                    time += 5;
+                }
+                let chars: Vec<char, 8> = converter.produce_chars().unwrap();
+                out.extend_from_slice(&chars).unwrap();
+            }
+        }
+        converter
+            .add_sample(SampledLightIntensity {
+                intensity: 450,
+                sample_time: time,
+            })
+            .unwrap();
+        let chars: Vec<char, 8> = converter.produce_chars().unwrap();
+        out.extend_from_slice(&chars).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_cutoff_tracking() {
+        let expected: Vec<char, 32> = "paris paris paris ".chars().collect();
+        let tracking = CutoffTrackerConfig {
+            smoothing: 16,
+            min_spread: 100,
+        };
+        assert_eq!(expected, helper_dimming_decode(Some(tracking)));
+
+        // With fixed cutoffs the dimmed light never counts as light, so the
+        // decoder sits in the gap after "pari" for good
+        let fixed: Vec<char, 32> = "pari".chars().collect();
+        assert_eq!(fixed, helper_dimming_decode(None));
+    }
+
+    // A sample every 5ms, with one flipped in the middle of the first dash
+    // and another in the middle of the word gap
+    fn helper_glitch_decode(deglitch: Option<DeglitchConfig>) -> Vec<char, 32> {
+        let key = construct_key();
+        let mut converter: MorseConverter<512, 512, 512> = MorseConverter::new(
+            0,
+            MorseUnitTimeDecision::EstimateProvided(20),
+            IntensityCutoffs {
+                low: 300,
+                high: 700,
+            },
+            None,
+        );
+        converter.set_deglitch(deglitch);
+
+        let mut time = 0;
+        let mut flipped = (false, false);
+        for pair in MorseEncoder::new("paris e", key).timed(20) {
+            let (morse, tle) = pair.unwrap();
+            let mut flip_at = None;
+            if morse == Morse::Dash && !flipped.0 {
+                flipped.0 = true;
+                flip_at = Some(time + 30);
+            } else if morse == Morse::WordSpace && !flipped.1 {
+                flipped.1 = true;
+                flip_at = Some(time + 70);
+            }
+            let end = time + tle.duration;
+            while time < end {
+                let light = (tle.light_state == LightState::Light) != (flip_at == Some(time));
+                converter
+                    .add_sample(SampledLightIntensity {
+                        intensity: if light { 900 } else { 100 },
+                        sample_time: time,
+                    })
+                    .unwrap();
+                time += 5;
+            }
+        }
+        converter
+            .add_sample(SampledLightIntensity {
+                intensity: 100,
+                sample_time: time,
+            })
+            .unwrap();
+        converter.produce_chars().unwrap()
+    }
+
+    #[test]
+    fn test_deglitch_decode() {
+        let deglitch = DeglitchConfig {
+            min_mark_ms: 10,
+            min_gap_ms: 10,
+        };
+        let expected: Vec<char, 32> = "paris ".chars().collect();
+        assert_eq!(expected, helper_glitch_decode(Some(deglitch)));
+        assert_ne!(expected, helper_glitch_decode(None));
+    }
+
+    fn helper_flush_converter() -> MorseConverter<'static, 128, 128, 128> {
+        MorseConverter::new(
+            0,
+            MorseUnitTimeDecision::EstimateProvided(20),
+            IntensityCutoffs {
+                low: 200,
+                high: 800,
+            },
+            None,
+        )
+    }
+
+    fn helper_add_samples(
+        converter: &mut MorseConverter<128, 128, 128>,
+        samples: &[SampledLightIntensity],
+    ) {
+        for sample in samples.iter() {
+            converter.add_sample(*sample).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_flush() {
+        let key = construct_key();
+        let mut converter = helper_flush_converter();
+        let mut time = 0;
+        for _ in 0..2 {
+            let mut samples: Vec<_, 64> = Vec::new();
+            helper_encode_samples(
+                "paris",
+                key,
+                MorseTiming::from_unit(20),
+                &mut time,
+                &mut samples,
+            );
+            samples
+                .push(SampledLightIntensity {
+                    intensity: 100,
+                    sample_time: time,
+                })
+                .unwrap();
+            time += 500;
+            helper_add_samples(&mut converter, &samples);
+
+            let chars: Vec<char, 32> = converter.produce_chars().unwrap();
+            assert_eq!(&['p', 'a', 'r', 'i'], &chars[..]);
+            converter.flush().unwrap();
+            let chars: Vec<char, 32> = converter.produce_chars().unwrap();
+            assert_eq!(&['s', ' '], &chars[..]);
+        }
+        // Flushing again with nothing new adds nothing
+        converter.flush().unwrap();
+        let chars: Vec<char, 32> = converter.produce_chars().unwrap();
+        assert!(chars.is_empty());
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let key = construct_key();
+        let mut converter = helper_flush_converter();
+        converter.set_idle_timeout(Some(200));
+        let mut time = 0;
+        let mut samples: Vec<_, 64> = Vec::new();
+        helper_encode_samples(
+            "paris",
+            key,
+            MorseTiming::from_unit(20),
+            &mut time,
+            &mut samples,
+        );
+        helper_add_samples(&mut converter, &samples);
+
+        let mut out: Vec<char, 32> = Vec::new();
+        for _ in 0..10 {
+            converter
+                .add_sample(SampledLightIntensity {
+                    intensity: 100,
+                    sample_time: time,
+                })
+                .unwrap();
+            time += 50;
+            let chars: Vec<char, 8> = converter.produce_chars().unwrap();
+            out.extend_from_slice(&chars).unwrap();
+        }
+        let expected: Vec<char, 32> = "paris ".chars().collect();
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn test_manager_flush() {
+        let key = construct_key();
+        let mut manager: MorseManager<128, 64, 64, 64> = MorseManager::new(
+            500,
+            MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
+                guess_after_this_many_tles: 7,
+                max_guess_ms: 40,
+                min_guess_ms: 10,
+            }),
+        );
+        let mut samples = helper_text_samples("ee", key, MorseTiming::from_unit(20));
+        // Ends the last mark without starting another
+        samples.last_mut().unwrap().intensity = 100;
+        for sample in samples.iter() {
+            manager.add_sample(*sample).unwrap();
+        }
+
+        // Too few light changes to start decoding until the flush
+        let chars: Vec<char, 8> = manager.produce_chars().unwrap();
+        assert!(chars.is_empty());
+        manager.flush().unwrap();
+        let chars: Vec<char, 8> = manager.produce_chars().unwrap();
+        assert_eq!(&['e', 'e', ' '], &chars[..]);
+    }
+
+    #[test]
+    fn test_manager_produce_chars_with() {
+        let key = construct_key();
+        let samples = helper_text_samples("the quick brown fox ", key, MorseTiming::from_unit(20));
+        let unit_time = MorseUnitTimeDecision::EstimateProvided(20);
+        // All of it comes out at once, more than a small Vec holds
+        let mut manager: MorseManager<256, 256, 256, 256> = MorseManager::new(500, unit_time);
+        for sample in samples.iter() {
+            manager.add_sample(*sample).unwrap();
+        }
+        let chars: Result<Vec<char, 8>, _> = manager.produce_chars();
+        assert_eq!(Err(MorseErr::InputTooLarge), chars);
+
+        let mut manager: MorseManager<256, 256, 256, 256> = MorseManager::new(500, unit_time);
+        for sample in samples.iter() {
+            manager.add_sample(*sample).unwrap();
+        }
+        let mut text: Vec<char, 32> = Vec::new();
+        manager
+            .produce_chars_with(|c| text.push(c).unwrap())
+            .unwrap();
+        manager.flush().unwrap();
+        manager
+            .produce_chars_with(|c| text.push(c).unwrap())
+            .unwrap();
+        assert!("the quick brown fox "
+            .chars()
+            .eq(text.iter().copied().take(20)));
+    }
+
+    #[test]
+    fn test_manager_leading_idle() {
+        let key = construct_key();
+        for intensity in [100, 900].iter() {
+            let mut manager: MorseManager<128, 64, 64, 64> = MorseManager::new(
+                500,
+                MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
+                    guess_after_this_many_tles: 7,
+                    max_guess_ms: 40,
+                    min_guess_ms: 10,
+                }),
+            );
+            // Far more idle than the warm up buffer holds, light or dark
+            let mut samples: Vec<_, 512> = Vec::new();
+            for sample_time in 0..300 {
+                samples
+                    .push(SampledLightIntensity {
+                        intensity: *intensity,
+                        sample_time,
+                    })
+                    .unwrap();
+            }
+            samples
+                .push(SampledLightIntensity {
+                    intensity: 100,
+                    sample_time: 300,
+                })
+                .unwrap();
+            let mut time = 400;
+            helper_encode_samples(
+                "paris ",
+                key,
+                MorseTiming::from_unit(20),
+                &mut time,
+                &mut samples,
+            );
+            helper_close_samples(time, &mut samples);
+            for sample in samples.iter() {
+                manager.add_sample(*sample).unwrap();
+            }
+            let chars: Vec<char, 8> = manager.produce_chars().unwrap();
+            assert_eq!(&['p', 'a', 'r', 'i', 's', ' '], &chars[..]);
+        }
+    }
+
+    // Streams "paris paris " at a 20ms unit and then three more at 40ms
+    // through a manager that has to work out the unit time itself
+    fn helper_relock_decode(relock: Option<RelockConfig>) -> (Vec<char, 128>, Option<Time>) {
+        let key = construct_key();
+        let mut time = 60;
+        let mut samples: Vec<_, 256> = Vec::new();
+        helper_encode_samples(
+            "paris paris ",
+            key,
+            MorseTiming::from_unit(20),
+            &mut time,
+            &mut samples,
+        );
+        helper_encode_samples(
+            "paris paris paris ",
+            key,
+            MorseTiming::from_unit(40),
+            &mut time,
+            &mut samples,
+        );
+        helper_close_samples(time, &mut samples);
+
+        let mut manager: MorseManager<128, 128, 128, 64> = MorseManager::new(
+            500,
+            MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
+                guess_after_this_many_tles: 7,
+                max_guess_ms: 60,
+                min_guess_ms: 10,
+            }),
+        );
+        manager.set_replacement(Some('?'));
+        manager.set_relock(relock);
+        let mut out = Vec::new();
+        for sample in samples.iter() {
+            manager.add_sample(*sample).unwrap();
+            let chars: Vec<char, 8> = manager.produce_chars().unwrap();
+            out.extend_from_slice(&chars).unwrap();
+        }
+        (out, manager.unit_time_estimate())
+    }
+
+    #[test]
+    fn test_relock() {
+        let relock = RelockConfig {
+            window: 20,
+            max_bad: 3,
+            min_confidence: 30,
+        };
+        let (chars, estimate) = helper_relock_decode(Some(relock));
+        assert!(chars.ends_with(&['p', 'a', 'r', 'i', 's', ' ', 'p', 'a', 'r', 'i', 's', ' ']));
+        let estimate = estimate.unwrap();
+        assert!(estimate > 35 && estimate < 45);
+
+        let (chars, estimate) = helper_relock_decode(None);
+        assert!(!chars.ends_with(&['p', 'a', 'r', 'i', 's', ' ']));
+        assert!(estimate.unwrap() < 25);
+    }
+
+    #[test]
+    fn test_manager_reset() {
+        let mut manager: MorseManager<128, 64, 64, 64> = MorseManager::new(
+            500,
+            MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
+                guess_after_this_many_tles: 7,
+                max_guess_ms: 40,
+                min_guess_ms: 10,
+            }),
+        );
+        let key = construct_key();
+        let timing = MorseTiming::from_unit(20);
+        let chars = helper_decode_text(&mut manager, "paris ", key, timing).unwrap();
+        assert_eq!(&['p', 'a', 'r', 'i', 's', ' '], &chars[..]);
+        assert!(manager.cutoffs().is_some());
+        assert!(manager.unit_time_estimate().is_some());
+
+        manager.reset();
+        assert_eq!(None, manager.cutoffs());
+        assert_eq!(None, manager.unit_time_estimate());
+
+        // A reset drops the letter in progress unless it is flushed out first
+        for flush in [false, true].iter() {
+            manager.reset();
+            let mut samples = helper_text_samples("paris", key, timing);
+            // Ends the last mark, but not the letter
+            samples.last_mut().unwrap().intensity = 100;
+            for sample in samples.iter() {
+                manager.add_sample(*sample).unwrap();
+            }
+            let mut chars: Vec<char, 8> = manager.produce_chars().unwrap();
+            if *flush {
+                manager.flush().unwrap();
+                let held: Vec<char, 8> = manager.produce_chars().unwrap();
+                chars.extend_from_slice(&held).unwrap();
+            }
+            manager.reset();
+            match flush {
+                true => assert_eq!(&['p', 'a', 'r', 'i', 's', ' '], &chars[..]),
+                false => assert_eq!(&['p', 'a', 'r', 'i'], &chars[..]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_farnsworth_timing() {
+        assert_eq!(
+            MorseTiming {
+                unit_ms: 60,
+                spacing_ms: 217
+            },
+            MorseTiming::farnsworth(20, 10)
+        );
+        assert_eq!(MorseTiming::from_unit(60), MorseTiming::farnsworth(20, 20));
+        assert_eq!(MorseTiming::from_unit(100), MorseTiming::from_wpm(12));
+    }
+
+    #[test]
+    fn test_best_error_timed() {
+        let gap = TimedLightEvent {
+            light_state: LightState::Dark,
+            duration: 651,
+        };
+        let timing = MorseTiming::farnsworth(20, 10);
+        let best = best_error_timed(&gap, timing).unwrap().item;
+        assert_eq!(Morse::LetterSpace, mc_to_morse(best).unwrap());
+        let best = best_error(&gap, timing.unit_ms).unwrap().item;
+        assert_eq!(Morse::WordSpace, mc_to_morse(best).unwrap());
+
+        let tiny = TimedLightEvent {
+            light_state: LightState::Dark,
+            duration: 60,
+        };
+        let best = best_error_timed(&tiny, timing).unwrap().item;
+        assert_eq!(Morse::TinySpace, mc_to_morse(best).unwrap());
+    }
+
+    #[test]
+    fn test_farnsworth_decode() {
+        let timing = MorseTiming::farnsworth(20, 10);
+        let samples = helper_text_samples("paris paris ", construct_key(), timing);
+
+        let derive = DeriveUnitTimeConfig {
+            guess_after_this_many_tles: 40,
+            max_guess_ms: 200,
+            min_guess_ms: 20,
+        };
+        let farnsworth = MorseUnitTimeDecision::TimingToBeDetermined(DeriveTimingConfig {
+            derive,
+            max_spacing_ms: 400,
+        });
+        let (chars, estimate) = helper_stream_decode(samples.clone(), farnsworth);
+        let expected: Vec<char, 32> = "paris paris ".chars().collect();
+        assert_eq!(expected, chars);
+        let estimate = estimate.unwrap();
+        assert!((estimate.unit_ms - 60).abs() < 10);
+        assert!((estimate.spacing_ms - 217).abs() < 20);
+
+        let single = MorseUnitTimeDecision::EstimateToBeDetermined(derive);
+        let (chars, _) = helper_stream_decode(samples, single);
+        assert_ne!(expected, chars);
+    }
+
+    #[test]
+    fn test_recover_unknown_char() {
+        // Six dashes isn't in the Latin key
+        let send_key = construct_custom_key(&[((1, 0), 'e'), ((6, 0b00111111), '#')]).unwrap();
+        let timing = MorseTiming::from_unit(20);
+
+        let cuts = IntensityCutoffs {
+            low: 200,
+            high: 800,
+        };
+        let unit_time = MorseUnitTimeDecision::EstimateProvided(20);
+        let mut strict: MorseConverter<64, 64, 64> = MorseConverter::new(0, unit_time, cuts, None);
+        let mut lenient: MorseConverter<64, 64, 64> = MorseConverter::new(0, unit_time, cuts, None);
+        lenient.set_replacement(Some('?'));
+
+        let bad = MorseErr::UnknownChar((6, 0b00111111));
+        assert_eq!(
+            Err(bad),
+            helper_decode_text(&mut strict, "e# e ", &send_key, timing)
+        );
+
+        let vec = helper_decode_text(&mut lenient, "e# e ", &send_key, timing).unwrap();
+        assert_eq!(&['e', '?', ' ', 'e', ' '], &vec[..]);
+        assert_eq!(
+            Some(RecoveredErr {
+                err: bad,
+                char_index: 1
+            }),
+            lenient.pop_recovered_err()
+        );
+        assert_eq!(None, lenient.pop_recovered_err());
+    }
+
+    #[test]
+    fn test_classification_confidence() {
+        let timing = MorseTiming::from_unit(20);
+        let tle = |light_state, duration| TimedLightEvent {
+            light_state,
+            duration,
+        };
+        assert_eq!(
+            100,
+            classification_confidence(&tle(LightState::Light, 20), timing)
+        );
+        assert_eq!(
+            0,
+            classification_confidence(&tle(LightState::Light, 40), timing)
+        );
+        assert_eq!(
+            25,
+            classification_confidence(&tle(LightState::Light, 45), timing)
+        );
+        assert_eq!(
+            100,
+            classification_confidence(&tle(LightState::Dark, 5000), timing)
+        );
+    }
+
+    #[test]
+    fn test_produce_decoded() {
+        let mut samples = helper_text_samples("te ", construct_key(), MorseTiming::from_unit(20));
+        // A sloppy 45ms dash for the t
+        samples[1].sample_time = 105;
+
+        let mut converter: MorseConverter<64, 64, 64> = MorseConverter::new(
+            0,
+            MorseUnitTimeDecision::EstimateProvided(20),
+            IntensityCutoffs {
+                low: 200,
+                high: 800,
+            },
+            None,
+        );
+        for sli in samples {
+            converter.add_sample(sli).unwrap();
+        }
+
+        let vec: Vec<_, 32> = converter.produce_decoded().unwrap();
+        assert_eq!(
+            &[
+                DecodedChar {
+                    c: 't',
+                    confidence: 25,
+                    start: 60,
+                    end: 105,
+                    unit_ms: 20,
+                    erases: 0
+                },
+                DecodedChar {
+                    c: 'e',
+                    confidence: 100,
+                    start: 180,
+                    end: 200,
+                    unit_ms: 20,
+                    erases: 0
+                },
+                DecodedChar {
+                    c: ' ',
+                    confidence: 100,
+                    start: 200,
+                    end: 340,
+                    unit_ms: 20,
+                    erases: 0
+                },
+            ],
+            &vec[..]
+        );
+    }
+
+    #[test]
+    fn test_manager_decoded_times() {
+        let key = construct_key();
+        let mut manager: MorseManager<128, 64, 64, 64> =
+            MorseManager::new(500, MorseUnitTimeDecision::EstimateProvided(20));
+        let mut samples = helper_text_samples("ee", key, MorseTiming::from_unit(20));
+        // Ends the last mark, and the gap after it runs on to 400ms
+        samples.last_mut().unwrap().intensity = 100;
+        samples
+            .push(SampledLightIntensity {
+                intensity: 100,
+                sample_time: 400,
+            })
+            .unwrap();
+        for sample in samples.iter() {
+            manager.add_sample(*sample).unwrap();
+        }
+        manager.flush().unwrap();
+
+        // The flushed word break runs from the last mark to the last sample
+        let vec: Vec<_, 8> = manager.produce_decoded().unwrap();
+        let times: Vec<_, 8> = vec.iter().map(|d| (d.c, d.start, d.end)).collect();
+        assert_eq!(
+            &[('e', 60, 80), ('e', 140, 160), (' ', 160, 400)],
+            &times[..]
+        );
+        assert!(vec.iter().all(|d| d.unit_ms == 20));
+    }
+
+    #[test]
+    fn test_manager_with_key() {
+        let key = construct_key_for(MorseAlphabet::Cyrillic);
+        let mut manager: MorseManager<128, 64, 64, 64> =
+            MorseManager::new_with_key(500, MorseUnitTimeDecision::EstimateProvided(20), key);
+
+        let timing = MorseTiming::from_unit(20);
+        let vec = helper_decode_text(&mut manager, "мир ", key, timing).unwrap();
+        assert_eq!(&['м', 'и', 'р', ' '], &vec[..]);
+    }
+
+    #[test]
+    fn test_manager_with_custom_key() {
+        // Only borrowed, like a table read in at run time would be
+        let key = construct_custom_key(&[((1, 0), 'x'), ((1, 1), 'y'), ((2, 2), 'z')]).unwrap();
+        let mut manager: MorseManager<128, 64, 64, 64> =
+            MorseManager::new_with_key(500, MorseUnitTimeDecision::EstimateProvided(20), &key);
+
+        let timing = MorseTiming::from_unit(20);
+        let vec = helper_decode_text(&mut manager, "xy zx ", &key, timing).unwrap();
+        assert_eq!(&['x', 'y', ' ', 'z', 'x', ' '], &vec[..]);
+    }
 }
 
 pub fn mc_to_morse(mc: &MorseCandidate) -> Result<Morse, MorseErr> {
@@ -1194,22 +3491,3 @@ pub fn mc_to_morse(mc: &MorseCandidate) -> Result<Morse, MorseErr> {
         _ => Err(MorseErr::InvalidMorseCandidate(mc.clone())),
     }
 }
-
-// fn char_to_morse(c: char) -> Morse {
-//     use Morse::*;
-//     match c {
-//         '0' => Dot,
-//         '1' => Dash,
-//         _ => Error,
-//     }
-// }
-
-// fn string_to_code(code: &str) -> Vec<Morse, U8> {
-//     code.chars().map(char_to_morse).collect()
-// }
-
-// fn ez(o: (&str, &char)) -> (Vec<Morse, U8>, char) {
-//     match o {
-//         (str, c) => (string_to_code(str), *c),
-//     }
-// }
@@ -1 +1,470 @@
-fn main() -> () {}
+use std::env;
+use std::fs;
+use std::process;
+
+use morse_utils::*;
+
+const USAGE: &str = "usage:
+    morse_utils decode <capture> [options]
+    morse_utils convert <dump> <capture> [options]
+    morse_utils render <text> <out> [options]
+
+decode runs a capture or a WAV recording through MorseManager and prints
+the decoded text, the cutoffs and the unit time. In a recording, the loudness
+of the tone stands in for the light. convert writes a bare dump, with one
+light intensity per line, out as a capture with a header. render sends
+text as a WAV recording, as raw samples or as a capture.
+
+options for bare dumps:
+    --period <ms>         time between samples [1]
+    --timestamps          each line starts with its sample time in ms
+    --width <bits>        bits in a reading [16]
+    --polarity <p>        light-high or light-low [light-high]
+
+options for WAV recordings:
+    --tone <hz>           the tone's frequency [the loudest from 300 to 1200,
+                          or 600 to render]
+    --block <ms>          audio measured for each sample [5]
+
+decode options:
+    --middle <n>          likely_middle, between the light and dark levels
+                          [halfway between the dimmest and brightest sample]
+    --guess-after <n>     light events to see before guessing the unit [20]
+    --min-unit <ms>       shortest unit time to guess [10]
+    --max-unit <ms>       longest unit time to guess [200]
+    --deglitch <ms>[,<ms>]
+                          merge marks, then gaps, shorter than this into the
+                          event around them [off]
+    --track-cutoffs <n>   let the cutoffs follow the light and dark levels,
+                          each sample moving them 1/n of the way [off]
+
+convert and render options:
+    --meta <key>=<value>  a metadata line for the header, can be repeated
+
+render options:
+    --wpm <n>             character speed [20]
+    --effective-wpm <n>   slower overall speed, with Farnsworth spacing
+    --rate <hz>           audio samples per second [8000]
+    --rise <ms>           how long each mark fades in and out [5]
+    --pad <ms>            silence before and after the text [150]
+    --raw                 headerless 16 bit little-endian samples, not WAV
+    --light <ms>          a capture sampled every <ms>, not audio";
+
+// The manager skips the idle stretch a capture starts with, so the warm up
+// only has to cover the first few light changes
+type Manager = MorseManager<'static, 256, 1024, 1024, 4096>;
+
+// Where to look for the tone when it isn't given
+const TONE_MIN_HZ: u32 = 300;
+const TONE_MAX_HZ: u32 = 1200;
+const TONE_STEP_HZ: u32 = 10;
+
+const RENDER_TONE_HZ: u32 = 600;
+// Half of full scale, leaving room for whatever it is mixed with
+const RENDER_AMPLITUDE: i16 = i16::MAX / 2;
+const RENDER_LEVELS: (LightIntensity, LightIntensity) = (100, 900);
+
+// Levels any closer than this are taken as a pause, not the ambient light
+// changing
+const TRACK_MIN_SPREAD: LightIntensity = 100;
+
+struct Args {
+    paths: Vec<String>,
+    // How to read a bare dump, and the header convert gives it
+    dump: CaptureHeader,
+    tone_hz: Option<u32>,
+    block_ms: Time,
+    likely_middle: Option<LightIntensity>,
+    derive: DeriveUnitTimeConfig,
+    deglitch: Option<DeglitchConfig>,
+    track_cutoffs: Option<CutoffTrackerConfig>,
+    metadata: Vec<(String, String)>,
+    wpm: Time,
+    effective_wpm: Option<Time>,
+    sample_rate: u32,
+    rise_ms: Time,
+    pad_ms: Time,
+    raw: bool,
+    light_period: Option<Time>,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("{} can't be {:?}", flag, value))
+}
+
+fn is_meta_key(key: &str) -> bool {
+    !key.is_empty() && !key.contains(char::is_whitespace)
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        paths: Vec::new(),
+        dump: CaptureHeader::legacy(1),
+        tone_hz: None,
+        block_ms: 5,
+        likely_middle: None,
+        derive: DeriveUnitTimeConfig {
+            guess_after_this_many_tles: 20,
+            min_guess_ms: 10,
+            max_guess_ms: 200,
+        },
+        deglitch: None,
+        track_cutoffs: None,
+        metadata: Vec::new(),
+        wpm: 20,
+        effective_wpm: None,
+        sample_rate: 8000,
+        rise_ms: 5,
+        pad_ms: 150,
+        raw: false,
+        light_period: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--period" => {
+                parsed.dump.timing = CaptureTiming::Periodic(parse_value(&arg, args.next())?)
+            }
+            "--timestamps" => parsed.dump.timing = CaptureTiming::Timed,
+            "--width" => match parse_value(&arg, args.next())? {
+                width @ 1..=16 => parsed.dump.width = width,
+                width => return Err(format!("--width can't be {}", width)),
+            },
+            "--polarity" => {
+                let name: String = parse_value(&arg, args.next())?;
+                parsed.dump.polarity = Polarity::from_name(&name)
+                    .ok_or_else(|| format!("--polarity can't be {:?}", name))?
+            }
+            "--tone" => parsed.tone_hz = Some(parse_value(&arg, args.next())?),
+            "--block" => parsed.block_ms = parse_value(&arg, args.next())?,
+            "--middle" => parsed.likely_middle = Some(parse_value(&arg, args.next())?),
+            "--guess-after" => {
+                parsed.derive.guess_after_this_many_tles = parse_value(&arg, args.next())?
+            }
+            "--min-unit" => parsed.derive.min_guess_ms = parse_value(&arg, args.next())?,
+            "--max-unit" => parsed.derive.max_guess_ms = parse_value(&arg, args.next())?,
+            "--deglitch" => {
+                let ms: String = parse_value(&arg, args.next())?;
+                let (mark, gap) = ms.split_once(',').unwrap_or((&ms, &ms));
+                parsed.deglitch = Some(DeglitchConfig {
+                    min_mark_ms: parse_value(&arg, Some(mark.to_string()))?,
+                    min_gap_ms: parse_value(&arg, Some(gap.to_string()))?,
+                })
+            }
+            "--track-cutoffs" => {
+                parsed.track_cutoffs = Some(CutoffTrackerConfig {
+                    smoothing: parse_value(&arg, args.next())?,
+                    min_spread: TRACK_MIN_SPREAD,
+                })
+            }
+            "--meta" => {
+                let meta: String = parse_value(&arg, args.next())?;
+                match meta.split_once('=') {
+                    // The key ends at the first space in the header line
+                    Some((key, value)) if is_meta_key(key.trim()) => parsed
+                        .metadata
+                        .push((key.trim().to_string(), value.trim().to_string())),
+                    _ => return Err(format!("--meta can't be {:?}", meta)),
+                }
+            }
+            "--wpm" => parsed.wpm = parse_value(&arg, args.next())?,
+            "--effective-wpm" => parsed.effective_wpm = Some(parse_value(&arg, args.next())?),
+            "--rate" => parsed.sample_rate = parse_value(&arg, args.next())?,
+            "--rise" => parsed.rise_ms = parse_value(&arg, args.next())?,
+            "--pad" => parsed.pad_ms = parse_value(&arg, args.next())?,
+            "--raw" => parsed.raw = true,
+            "--light" => parsed.light_period = Some(parse_value(&arg, args.next())?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => parsed.paths.push(arg),
+        }
+    }
+    Ok(parsed)
+}
+
+fn expect_paths(args: &Args, count: usize) -> Result<(), String> {
+    match args.paths.len() {
+        n if n < count => Err(String::from("not enough files given")),
+        n if n > count => Err(format!("unexpected argument {}", args.paths[count])),
+        _ => Ok(()),
+    }
+}
+
+// Captures are read with their own header, anything else as a bare dump
+fn read_samples(args: &Args, text: &str) -> Result<Vec<SampledLightIntensity>, CaptureErr> {
+    if text.starts_with(CAPTURE_MAGIC) {
+        CaptureReader::new(text)?.collect()
+    } else {
+        CaptureReader::legacy(text, args.dump).collect()
+    }
+}
+
+// The tone's loudness through the recording, and the tone it followed
+fn read_audio(args: &Args, bytes: &[u8]) -> Result<(Vec<SampledLightIntensity>, u32), WavErr> {
+    let wav = Wav::parse(bytes)?;
+    let tone_hz = args
+        .tone_hz
+        .or_else(|| {
+            detect_tone(
+                wav.samples(),
+                wav.sample_rate,
+                TONE_MIN_HZ,
+                TONE_MAX_HZ,
+                TONE_STEP_HZ,
+            )
+        })
+        .unwrap_or(TONE_MIN_HZ);
+    let config = ToneConfig {
+        tone_hz,
+        block_ms: args.block_ms,
+    };
+    let samples = ToneEnvelope::new(wav.samples(), wav.sample_rate, config).collect();
+    Ok((samples, tone_hz))
+}
+
+fn halfway(samples: &[SampledLightIntensity]) -> LightIntensity {
+    let levels = samples.iter().map(|s| s.intensity);
+    let (low, high) = (levels.clone().min(), levels.max());
+    ((low.unwrap_or(0) as u32 + high.unwrap_or(0) as u32) / 2) as LightIntensity
+}
+
+fn decode(args: Args) -> Result<(), String> {
+    expect_paths(&args, 1)?;
+    let path = &args.paths[0];
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let audio = bytes.starts_with(b"RIFF");
+    let samples = if audio {
+        let (samples, tone_hz) =
+            read_audio(&args, &bytes).map_err(|e| format!("{}: {:?}", path, e))?;
+        println!("tone: {} Hz", tone_hz);
+        samples
+    } else {
+        let text = String::from_utf8_lossy(&bytes);
+        read_samples(&args, &text).map_err(|e| format!("{}: {:?}", path, e))?
+    };
+    let likely_middle = args.likely_middle.unwrap_or_else(|| halfway(&samples));
+
+    let mut manager: Manager = MorseManager::new(
+        likely_middle,
+        MorseUnitTimeDecision::EstimateToBeDetermined(args.derive),
+    );
+    manager.set_replacement(Some('?'));
+    manager.set_deglitch(args.deglitch);
+    manager.set_cutoff_tracking(args.track_cutoffs);
+
+    let mut text = String::new();
+    for sample in samples.iter() {
+        let at = sample.sample_time;
+        manager
+            .add_sample(*sample)
+            .map_err(|e| format!("at {} ms: {:?}", at, e))?;
+        manager
+            .produce_chars_with(|c| text.push(c))
+            .map_err(|e| format!("at {} ms: {:?}", at, e))?;
+    }
+    manager
+        .flush()
+        .map_err(|e| format!("at the end: {:?}", e))?;
+    manager
+        .produce_chars_with(|c| text.push(c))
+        .map_err(|e| format!("at the end: {:?}", e))?;
+
+    println!("text: {}", text.trim_end());
+    match manager.cutoffs() {
+        Some(cuts) => println!("cutoffs: low {} high {}", cuts.low, cuts.high),
+        None => println!("cutoffs: not enough light changes to work out"),
+    }
+    match manager.timing_estimate() {
+        Some(timing) if timing.spacing_ms != timing.unit_ms => println!(
+            "unit time: {} ms, spacing {} ms",
+            timing.unit_ms, timing.spacing_ms
+        ),
+        Some(timing) => println!("unit time: {} ms", timing.unit_ms),
+        None => println!("unit time: not locked"),
+    }
+    while let Some(recovered) = manager.pop_recovered_err() {
+        eprintln!("recovered: {:?}", recovered);
+    }
+    Ok(())
+}
+
+fn convert_dump(args: &Args, dump: &str) -> Result<String, CaptureErr> {
+    let metadata: Vec<(&str, &str)> = args
+        .metadata
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    let mut capture = String::new();
+    args.dump.write(&mut capture, &metadata)?;
+    for sample in CaptureReader::legacy(dump, args.dump) {
+        // Writing to a String can't fail
+        args.dump.write_sample(&mut capture, &sample?).unwrap();
+    }
+    Ok(capture)
+}
+
+fn convert(args: Args) -> Result<(), String> {
+    expect_paths(&args, 2)?;
+    let (from, to) = (&args.paths[0], &args.paths[1]);
+    let dump = fs::read_to_string(from).map_err(|e| format!("{}: {}", from, e))?;
+    if dump.starts_with(CAPTURE_MAGIC) {
+        return Err(format!("{} already has a header", from));
+    }
+    let capture = convert_dump(&args, &dump).map_err(|e| format!("{}: {:?}", from, e))?;
+    fs::write(to, capture).map_err(|e| format!("{}: {}", to, e))
+}
+
+// The text as it would be keyed, with the padding either side
+fn render_events(args: &Args, text: &str) -> Result<Vec<TimedLightEvent>, MorseErr> {
+    let timing = match args.effective_wpm {
+        Some(effective) => MorseTiming::farnsworth(args.wpm, effective),
+        None => MorseTiming::from_wpm(args.wpm),
+    };
+    let pad = TimedLightEvent {
+        light_state: LightState::Dark,
+        duration: args.pad_ms,
+    };
+    let mut events = vec![pad];
+    for pair in MorseEncoder::new(text, construct_key()).timed_with(timing) {
+        events.push(pair?.1);
+    }
+    events.push(pad);
+    Ok(events)
+}
+
+fn render_light(
+    args: &Args,
+    text: &str,
+    events: Vec<TimedLightEvent>,
+    period: Time,
+) -> Result<String, CaptureErr> {
+    let header = CaptureHeader {
+        timing: CaptureTiming::Periodic(period),
+        ..args.dump
+    };
+    let levels = LightLevels {
+        dark: RENDER_LEVELS.0,
+        light: RENDER_LEVELS.1,
+        period_ms: period,
+    };
+    let mut metadata = vec![("text", text)];
+    metadata.extend(args.metadata.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+    let mut capture = String::new();
+    header.write(&mut capture, &metadata)?;
+    for sample in KeyedLight::new(events.into_iter(), levels) {
+        // Writing to a String can't fail
+        header.write_sample(&mut capture, &sample).unwrap();
+    }
+    Ok(capture)
+}
+
+fn render_audio(args: &Args, events: Vec<TimedLightEvent>) -> Vec<u8> {
+    let config = KeyingConfig {
+        tone_hz: args.tone_hz.unwrap_or(RENDER_TONE_HZ),
+        sample_rate: args.sample_rate,
+        amplitude: RENDER_AMPLITUDE,
+        rise_ms: args.rise_ms,
+    };
+    let samples: Vec<i16> = KeyedTone::new(events.into_iter(), config).collect();
+    let mut bytes = match args.raw {
+        true => Vec::new(),
+        false => wav_header(args.sample_rate, samples.len() as u32).to_vec(),
+    };
+    bytes.extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+    bytes
+}
+
+fn render(args: Args) -> Result<(), String> {
+    expect_paths(&args, 2)?;
+    let (text, to) = (&args.paths[0], &args.paths[1]);
+    let events = render_events(&args, text).map_err(|e| format!("{:?}", e))?;
+    let bytes = match args.light_period {
+        Some(period) => render_light(&args, text, events, period)
+            .map_err(|e| format!("{}: {:?}", to, e))?
+            .into_bytes(),
+        None => render_audio(&args, events),
+    };
+    fs::write(to, bytes).map_err(|e| format!("{}: {}", to, e))
+}
+
+fn run(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    match args.next().as_deref() {
+        Some("decode") => decode(parse_args(args)?),
+        Some("convert") => convert(parse_args(args)?),
+        Some("render") => render(parse_args(args)?),
+        Some(command) => Err(format!("unknown command {}", command)),
+        None => Err(String::from("no command given")),
+    }
+}
+
+fn main() {
+    if let Err(e) = run(env::args().skip(1)) {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper_args(args: &[&str]) -> Args {
+        parse_args(args.iter().map(|a| a.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_read_samples() {
+        let samples = read_samples(&helper_args(&["--period", "20"]), "900\n\n100\n").unwrap();
+        assert_eq!(20, samples[1].sample_time);
+        assert_eq!(100, samples[1].intensity);
+
+        let samples = read_samples(&helper_args(&["--timestamps"]), "5 900\n31 100").unwrap();
+        assert_eq!(31, samples[1].sample_time);
+
+        let args = helper_args(&[]);
+        assert_eq!(Err(CaptureErr::BadRow(1)), read_samples(&args, "5 900"));
+        assert_eq!(
+            Err(CaptureErr::BadRow(2)),
+            read_samples(&args, "900\nbright")
+        );
+        // The header wins over the options
+        let samples = read_samples(&args, "#morse-capture 1\n#period-ms 5\n1\n2\n").unwrap();
+        assert_eq!(5, samples[1].sample_time);
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        let args = helper_args(&["--deglitch", "8", "--track-cutoffs", "64"]);
+        let deglitch = DeglitchConfig {
+            min_mark_ms: 8,
+            min_gap_ms: 8,
+        };
+        assert_eq!(Some(deglitch), args.deglitch);
+        assert_eq!(Some(64), args.track_cutoffs.map(|c| c.smoothing));
+
+        let args = helper_args(&["--deglitch", "8,3"]);
+        assert_eq!(Some(3), args.deglitch.map(|d| d.min_gap_ms));
+        assert!(parse_args(["--deglitch", "8,"].iter().map(|a| a.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_convert_dump() {
+        let args = helper_args(&[
+            "--width",
+            "10",
+            "--polarity",
+            "light-low",
+            "--meta",
+            "sensor = photoresistor",
+        ]);
+        let capture = convert_dump(&args, "1000\n23\n").unwrap();
+        assert_eq!(
+            "#morse-capture 1\n#period-ms 1\n#width 10\n#polarity light-low\n\
+             #meta sensor photoresistor\n1000\n23\n",
+            capture
+        );
+        let samples = read_samples(&helper_args(&[]), &capture).unwrap();
+        assert_eq!(1000, samples[1].intensity);
+    }
+}
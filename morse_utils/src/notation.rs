@@ -0,0 +1,235 @@
+use crate::*;
+
+use core::fmt;
+
+// Turns dot-dash notation like ".-.. --- / ..." into Morse, the same shape
+// MorseEncoder gives for text: a '.' or '-' per element with TinySpace between
+// them, whitespace between letters becomes a LetterSpace and a '/' between
+// words a WordSpace. A gap at the end is kept, so the last letter is ended.
+pub struct MorseNotationParser<'a> {
+    chars: core::str::Chars<'a>,
+    gap: Option<Morse>,
+    pending: Option<Morse>,
+    last: Option<Morse>,
+}
+
+impl<'a> MorseNotationParser<'a> {
+    pub fn new(notation: &'a str) -> MorseNotationParser<'a> {
+        MorseNotationParser {
+            chars: notation.chars(),
+            gap: None,
+            pending: None,
+            last: None,
+        }
+    }
+
+    fn emit(&mut self, morse: Morse) -> Option<Result<Morse, MorseErr>> {
+        self.last = Some(morse);
+        Some(Ok(morse))
+    }
+
+    // The space owed before the next element, or at the end if there isn't one
+    fn take_gap(&mut self, before_mark: bool) -> Option<Morse> {
+        use Morse::*;
+        let after_mark = matches!(self.last, Some(Dot) | Some(Dash));
+        match self.gap.take() {
+            Some(WordSpace) if self.last != Some(WordSpace) => Some(WordSpace),
+            Some(LetterSpace) if after_mark => Some(LetterSpace),
+            None if after_mark && before_mark => Some(TinySpace),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Iterator for MorseNotationParser<'a> {
+    type Item = Result<Morse, MorseErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use Morse::*;
+        if let Some(m) = self.pending.take() {
+            return self.emit(m);
+        }
+
+        loop {
+            let c = match self.chars.next() {
+                Some(c) => c,
+                None => {
+                    let gap = self.take_gap(false)?;
+                    return self.emit(gap);
+                }
+            };
+            let element = match c {
+                '.' => Dot,
+                '-' => Dash,
+                '/' => {
+                    self.gap = Some(WordSpace);
+                    continue;
+                }
+                c if c.is_whitespace() => {
+                    if self.gap != Some(WordSpace) {
+                        self.gap = Some(LetterSpace);
+                    }
+                    continue;
+                }
+                c => return Some(Err(MorseErr::UnparsableNotation(c))),
+            };
+            if let Some(gap) = self.take_gap(true) {
+                self.pending = Some(element);
+                return self.emit(gap);
+            }
+            return self.emit(element);
+        }
+    }
+}
+
+// The code for a single letter, like "-.-." for c, for building keys
+pub fn parse_code(code: &str) -> Result<MorseSequenceSerialization, MorseErr> {
+    let mut marks: Vec<Morse, { MAX_CODE_LEN as usize }> = Vec::new();
+    for m in MorseNotationParser::new(code.trim()) {
+        let m = m?;
+        match m {
+            Morse::TinySpace => (),
+            Morse::Dot | Morse::Dash => marks.push(m).map_err(|_| MorseErr::InputTooLarge)?,
+            other => return Err(MorseErr::MorseInputCrossesLetterBound(other)),
+        }
+    }
+    serialize_morse(&marks[..])
+}
+
+// Written with no separator between the two elements of a TinySpace, so
+// MorseNotationParser reads back Morse shaped the way MorseEncoder makes it.
+// Elements next to each other without a TinySpace can't be told apart from
+// ones with, and read back with one between them.
+impl fmt::Display for Morse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Morse::*;
+        f.write_str(match self {
+            Dot => ".",
+            Dash => "-",
+            TinySpace => "",
+            LetterSpace => " ",
+            WordSpace => " / ",
+        })
+    }
+}
+
+// A run of Morse written out as dot-dash notation
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct MorseNotation<'a>(pub &'a [Morse]);
+
+impl<'a> fmt::Display for MorseNotation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|m| write!(f, "{}", m))
+    }
+}
+
+// A single letter's code written out, as parse_code reads it
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CodeNotation(pub MorseSequenceSerialization);
+
+impl fmt::Display for CodeNotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (len, bits) = self.0;
+        (0..len.min(MAX_CODE_LEN))
+            .try_for_each(|i| f.write_str(if bits & (1 << i) != 0 { "-" } else { "." }))
+    }
+}
+
+// Text, such as decoded letters, written out the way the key would send it.
+// A char the key can't send fails the formatting.
+#[derive(Clone, Copy, Debug)]
+pub struct TextNotation<'a> {
+    pub text: &'a str,
+    pub mkey: &'a MorseKey,
+}
+
+impl<'a> TextNotation<'a> {
+    pub fn new(text: &'a str, mkey: &'a MorseKey) -> TextNotation<'a> {
+        TextNotation { text, mkey }
+    }
+}
+
+impl<'a> fmt::Display for TextNotation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        MorseEncoder::new(self.text, self.mkey)
+            .try_for_each(|m| write!(f, "{}", m.map_err(|_| fmt::Error)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+    use heapless::{String, Vec};
+    use Morse::*;
+
+    #[test]
+    fn test_parse_notation() {
+        let morses: Result<Vec<_, 32>, _> = MorseNotationParser::new(" .-  -.. / . ").collect();
+        assert_eq!(
+            &[
+                Dot,
+                TinySpace,
+                Dash,
+                LetterSpace,
+                Dash,
+                TinySpace,
+                Dot,
+                TinySpace,
+                Dot,
+                WordSpace,
+                Dot,
+                LetterSpace
+            ],
+            &morses.unwrap()[..]
+        );
+
+        let mut parser = MorseNotationParser::new(".x");
+        assert_eq!(Some(Ok(Dot)), parser.next());
+        assert_eq!(Some(Err(MorseErr::UnparsableNotation('x'))), parser.next());
+    }
+
+    #[test]
+    fn test_parse_code() {
+        assert_eq!(Ok((4, 0b0101)), parse_code("-.-."));
+        assert_eq!(Ok((1, 0)), parse_code(" . "));
+        assert_eq!(
+            Err(MorseErr::MorseInputCrossesLetterBound(LetterSpace)),
+            parse_code(".. -")
+        );
+        assert_eq!(Err(MorseErr::EmptyInput), parse_code(""));
+        assert_eq!(
+            Err(MorseErr::InputTooLarge),
+            parse_code("-----------------")
+        );
+    }
+
+    #[test]
+    fn test_notation_round_trip() {
+        let key = construct_key();
+        let mut written: String<64> = String::new();
+        write!(written, "{}", TextNotation::new("lo s<SK>", key)).unwrap();
+        assert_eq!(".-.. --- / ... ...-.-", &written[..]);
+
+        let encoded: Result<Vec<_, 64>, _> = MorseEncoder::new("lo s<SK>", key).collect();
+        let parsed: Result<Vec<_, 64>, _> = MorseNotationParser::new(&written).collect();
+        assert_eq!(encoded.unwrap(), parsed.unwrap());
+
+        let mut code: String<16> = String::new();
+        let ser = key.serialization_of('c').unwrap();
+        write!(code, "{}", CodeNotation(ser)).unwrap();
+        assert_eq!("-.-.", &code[..]);
+        assert_eq!(Ok(ser), parse_code(&code));
+
+        let mut morses: String<16> = String::new();
+        write!(morses, "{}", MorseNotation(&[Dot, WordSpace, Dash])).unwrap();
+        assert_eq!(". / -", &morses[..]);
+
+        // Without the TinySpace the encoder would have put in, it comes back
+        let mut joined: String<16> = String::new();
+        write!(joined, "{}", MorseNotation(&[Dot, Dot, LetterSpace])).unwrap();
+        assert_eq!(".. ", &joined[..]);
+        let parsed: Result<Vec<_, 8>, _> = MorseNotationParser::new(&joined).collect();
+        assert_eq!(&[Dot, TinySpace, Dot, LetterSpace], &parsed.unwrap()[..]);
+    }
+}
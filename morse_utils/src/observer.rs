@@ -0,0 +1,107 @@
+use crate::*;
+
+// Called by MorseConverter and MorseManager as data moves through each stage,
+// for logging on targets without a println. Every method does nothing unless
+// overridden.
+pub trait MorseObserver {
+    // The cutoffs were worked out from the warm up samples, or moved by tracking
+    fn cutoffs_computed(&mut self, _cuts: IntensityCutoffs) {}
+    // A TimedLightEvent made it past deglitching and into the queue
+    fn tle_produced(&mut self, _tle: &TimedLightEvent) {}
+    fn morse_classified(
+        &mut self,
+        _tle: &TimedLightEvent,
+        _scored: Scored<&MorseCandidate>,
+        _morse: Morse,
+    ) {
+    }
+    // The unit time decision has been made
+    fn timing_locked(&mut self, _timing: MorseTiming) {}
+    // Once for each char handed back, so a prosign's name is several
+    fn letter_emitted(&mut self, _decoded: &DecodedChar) {}
+    // Both the errors returned and the ones recovered from with a replacement
+    fn error_raised(&mut self, _err: &MorseErr) {}
+    // MorseManager is going back to its warm up to relock
+    fn recalibrating(&mut self) {}
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct NoObserver;
+
+impl MorseObserver for NoObserver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helper_decode_text;
+    use heapless::Vec;
+
+    #[derive(PartialEq, Eq, Debug, Default)]
+    struct CountingObserver {
+        cutoffs: u32,
+        tles: u32,
+        morses: u32,
+        timings: Vec<MorseTiming, 4>,
+        letters: Vec<char, 16>,
+        errors: Vec<MorseErr, 4>,
+    }
+
+    impl MorseObserver for CountingObserver {
+        fn cutoffs_computed(&mut self, _cuts: IntensityCutoffs) {
+            self.cutoffs += 1;
+        }
+        fn tle_produced(&mut self, _tle: &TimedLightEvent) {
+            self.tles += 1;
+        }
+        fn morse_classified(
+            &mut self,
+            _tle: &TimedLightEvent,
+            _scored: Scored<&MorseCandidate>,
+            _morse: Morse,
+        ) {
+            self.morses += 1;
+        }
+        fn timing_locked(&mut self, timing: MorseTiming) {
+            self.timings.push(timing).unwrap();
+        }
+        fn letter_emitted(&mut self, decoded: &DecodedChar) {
+            self.letters.push(decoded.c).unwrap();
+        }
+        fn error_raised(&mut self, err: &MorseErr) {
+            self.errors.push(*err).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        // Six dashes isn't in the Latin key
+        let send_key = construct_custom_key(&[((1, 0), 'e'), ((6, 0b00111111), '#')]).unwrap();
+        let mut manager: MorseManager<128, 64, 64, 64, CountingObserver> =
+            MorseManager::new_with_observer(
+                500,
+                MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
+                    guess_after_this_many_tles: 7,
+                    max_guess_ms: 40,
+                    min_guess_ms: 10,
+                }),
+                construct_key(),
+                CountingObserver::default(),
+            );
+        manager.set_replacement(Some('?'));
+
+        let send_timing = MorseTiming::from_unit(20);
+        let chars = helper_decode_text(&mut manager, "e# e ", &send_key, send_timing).unwrap();
+
+        let timing = manager.timing_estimate().unwrap();
+        let observer = manager.observer();
+        assert_eq!(1, observer.cutoffs);
+        assert_eq!(observer.tles, observer.morses);
+        assert_eq!(&[timing], &observer.timings[..]);
+        assert_eq!(chars, observer.letters);
+        assert_eq!(&['e', '?', ' ', 'e', ' '], &chars[..]);
+        assert_eq!(
+            &[MorseErr::UnknownChar((6, 0b00111111))],
+            &observer.errors[..]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use crate::*;
+
+use core::f32::consts::PI;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct KeyingConfig {
+    pub tone_hz: u32,
+    pub sample_rate: u32,
+    // Peak of the tone, out of i16::MAX
+    pub amplitude: i16,
+    // How long each mark takes to fade in and out. Switching the tone on and
+    // off in an instant clicks.
+    pub rise_ms: Time,
+}
+
+// Sounds out TimedLightEvents as audio samples, a tone for each mark and
+// silence for each gap. The fades follow a raised cosine and sit inside the
+// mark, so the marks keep their length.
+pub struct KeyedTone<I> {
+    events: I,
+    config: KeyingConfig,
+    step: f32,
+    rise: u32,
+    phase: f32,
+    // Where the last event ends, so rounding to samples doesn't add up
+    end_ms: Time,
+    rendered: u64,
+    light: bool,
+    len: u32,
+    pos: u32,
+}
+
+impl<I: Iterator<Item = TimedLightEvent>> KeyedTone<I> {
+    pub fn new(events: I, config: KeyingConfig) -> KeyedTone<I> {
+        let rate = config.sample_rate.max(1);
+        KeyedTone {
+            events,
+            config,
+            step: 2.0 * PI * config.tone_hz.min(rate / 2) as f32 / rate as f32,
+            rise: (config.rise_ms.max(0) * rate as Time / 1000) as u32,
+            phase: 0.0,
+            end_ms: 0,
+            rendered: 0,
+            light: false,
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    fn gain(&self) -> f32 {
+        let rise = self.rise.min(self.len / 2);
+        let edge = self.pos.min(self.len - 1 - self.pos);
+        match edge < rise {
+            true => 0.5 - 0.5 * cos_half_turn(PI * edge as f32 / rise as f32),
+            false => 1.0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = TimedLightEvent>> Iterator for KeyedTone<I> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        while self.pos >= self.len {
+            let tle = self.events.next()?;
+            self.end_ms += tle.duration;
+            let end = (self.end_ms.max(0) as u64) * self.config.sample_rate as u64 / 1000;
+            self.len = end.saturating_sub(self.rendered) as u32;
+            self.pos = 0;
+            self.light = tle.light_state == LightState::Light;
+        }
+
+        let sample = match self.light {
+            true => self.config.amplitude as f32 * self.gain() * sin_turn(self.phase),
+            false => 0.0,
+        };
+        // Rounded, as the cast only truncates
+        let sample = sample + if sample < 0.0 { -0.5 } else { 0.5 };
+        self.phase += self.step;
+        if self.phase >= 2.0 * PI {
+            self.phase -= 2.0 * PI;
+        }
+        self.pos += 1;
+        self.rendered += 1;
+        Some(sample as i16)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct LightLevels {
+    pub dark: LightIntensity,
+    pub light: LightIntensity,
+    pub period_ms: Time,
+}
+
+// Samples TimedLightEvents the way the sensor would, every period_ms from 0,
+// to make captures the decoder can be run on
+pub struct KeyedLight<I> {
+    events: I,
+    levels: LightLevels,
+    time: Time,
+    end_ms: Time,
+    state: LightState,
+}
+
+impl<I: Iterator<Item = TimedLightEvent>> KeyedLight<I> {
+    pub fn new(events: I, levels: LightLevels) -> KeyedLight<I> {
+        KeyedLight {
+            events,
+            levels: LightLevels {
+                period_ms: levels.period_ms.max(1),
+                ..levels
+            },
+            time: 0,
+            end_ms: 0,
+            state: LightState::Dark,
+        }
+    }
+}
+
+impl<I: Iterator<Item = TimedLightEvent>> Iterator for KeyedLight<I> {
+    type Item = SampledLightIntensity;
+
+    fn next(&mut self) -> Option<SampledLightIntensity> {
+        while self.time >= self.end_ms {
+            let tle = self.events.next()?;
+            self.end_ms += tle.duration;
+            self.state = tle.light_state;
+        }
+        let sample = SampledLightIntensity {
+            sample_time: self.time,
+            intensity: match self.state {
+                LightState::Light => self.levels.light,
+                LightState::Dark => self.levels.dark,
+            },
+        };
+        self.time += self.levels.period_ms;
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::iter::once;
+    use heapless::Vec;
+
+    // Short enough not to be taken for a word space before the first letter
+    const PAD: TimedLightEvent = TimedLightEvent {
+        light_state: LightState::Dark,
+        duration: 150,
+    };
+
+    fn helper_events(text: &str, timing: MorseTiming) -> std::vec::Vec<TimedLightEvent> {
+        let events = MorseEncoder::new(text, construct_key()).timed_with(timing);
+        once(PAD)
+            .chain(events.map(|pair| pair.unwrap().1))
+            .chain(once(PAD))
+            .collect()
+    }
+
+    fn helper_decode<I>(samples: I, likely_middle: LightIntensity) -> Vec<char, 32>
+    where
+        I: Iterator<Item = SampledLightIntensity>,
+    {
+        let mut manager: MorseManager<512, 64, 64, 256> = MorseManager::new(
+            likely_middle,
+            MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
+                guess_after_this_many_tles: 7,
+                max_guess_ms: 150,
+                min_guess_ms: 20,
+            }),
+        );
+        let mut chars = Vec::new();
+        for sample in samples {
+            manager.add_sample(sample).unwrap();
+            let produced: Vec<char, 32> = manager.produce_chars().unwrap();
+            chars.extend(produced);
+        }
+        manager.flush().unwrap();
+        let produced: Vec<char, 32> = manager.produce_chars().unwrap();
+        chars.extend(produced);
+        chars
+    }
+
+    #[test]
+    fn test_keyed_tone_shape() {
+        let config = KeyingConfig {
+            tone_hz: 2000,
+            sample_rate: 8000,
+            amplitude: 10000,
+            rise_ms: 1,
+        };
+        let mark = TimedLightEvent {
+            light_state: LightState::Light,
+            duration: 5,
+        };
+        let gap = TimedLightEvent {
+            light_state: LightState::Dark,
+            ..mark
+        };
+        let samples: Vec<i16, 128> = KeyedTone::new([mark, gap].iter().copied(), config).collect();
+        assert_eq!(80, samples.len());
+        // A quarter of a cycle a sample, fading in over the first 8
+        assert_eq!(
+            &[0, 381, 0, -3087, 0, 6913, 0, -9619, 0, 10000],
+            &samples[..10]
+        );
+        assert_eq!(&[1464, 0, 0], &samples[37..40]);
+        assert!(samples[40..].iter().all(|s| *s == 0));
+    }
+
+    #[test]
+    fn test_render_wav() {
+        let config = KeyingConfig {
+            tone_hz: 600,
+            sample_rate: 8000,
+            amplitude: 16000,
+            rise_ms: 5,
+        };
+        let events = helper_events("cq de k1abc ", MorseTiming::from_wpm(20));
+        let audio: std::vec::Vec<i16> = KeyedTone::new(events.into_iter(), config).collect();
+        let mut bytes = wav_header(config.sample_rate, audio.len() as u32).to_vec();
+        audio
+            .iter()
+            .for_each(|s| bytes.extend(s.to_le_bytes().iter()));
+
+        let wav = Wav::parse(&bytes).unwrap();
+        let tone_hz = detect_tone(wav.samples(), wav.sample_rate, 300, 1200, 10).unwrap();
+        assert_eq!(600, tone_hz);
+        let envelope = ToneEnvelope::new(
+            wav.samples(),
+            wav.sample_rate,
+            ToneConfig {
+                tone_hz,
+                block_ms: 5,
+            },
+        );
+        let expected: Vec<char, 32> = "cq de k1abc ".chars().collect();
+        assert_eq!(expected, helper_decode(envelope, 8000));
+    }
+
+    #[test]
+    fn test_render_capture() {
+        let levels = LightLevels {
+            dark: 100,
+            light: 900,
+            period_ms: 2,
+        };
+        let header = CaptureHeader::legacy(levels.period_ms);
+        let mut capture: std::string::String = std::string::String::new();
+        header.write(&mut capture, &[("text", "sos")]).unwrap();
+        let events = helper_events("sos ", MorseTiming::from_unit(40));
+        for sample in KeyedLight::new(events.into_iter(), levels) {
+            header.write_sample(&mut capture, &sample).unwrap();
+        }
+
+        let samples = CaptureReader::new(&capture).unwrap().map(|s| s.unwrap());
+        let expected: Vec<char, 32> = "sos ".chars().collect();
+        assert_eq!(expected, helper_decode(samples, 500));
+
+        // The padding, then the first mark
+        let mut rows = capture.lines().skip(5);
+        rows.by_ref().take(75).for_each(|r| assert_eq!("100", r));
+        assert_eq!(Some("900"), rows.next());
+    }
+}
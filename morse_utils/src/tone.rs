@@ -0,0 +1,255 @@
+use crate::*;
+
+use core::f32::consts::PI;
+
+// core has no trig or square roots, and these are only needed once per
+// detector and once per block
+
+// For x from 0 to PI, where the series around PI/2 converges quickly
+pub(crate) fn cos_half_turn(x: f32) -> f32 {
+    let y = PI / 2.0 - x;
+    let mut term = y;
+    let mut sum = y;
+    for n in 1..8 {
+        term *= -y * y / ((2 * n) * (2 * n + 1)) as f32;
+        sum += term;
+    }
+    sum
+}
+
+// For x from 0 to 2 PI
+pub(crate) fn sin_turn(x: f32) -> f32 {
+    match x <= PI {
+        true => cos_half_turn((x - PI / 2.0).abs()),
+        false => -cos_half_turn((x - 3.0 * PI / 2.0).abs()),
+    }
+}
+
+fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    // Halving the exponent is close enough for Newton's method to finish
+    let mut r = f32::from_bits((x.to_bits() >> 1) + 0x1fc0_0000);
+    for _ in 0..4 {
+        r = 0.5 * (r + x / r);
+    }
+    r
+}
+
+// Measures one frequency in a block of audio samples, like a single bin of
+// a DFT without working out the others
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Goertzel {
+    coeff: f32,
+    block: u32,
+    count: u32,
+    s1: f32,
+    s2: f32,
+}
+
+impl Goertzel {
+    pub fn new(tone_hz: u32, sample_rate: u32, block: u32) -> Goertzel {
+        let tone_hz = tone_hz.min(sample_rate / 2);
+        Goertzel {
+            coeff: 2.0 * cos_half_turn(2.0 * PI * tone_hz as f32 / sample_rate as f32),
+            block: block.max(1),
+            count: 0,
+            s1: 0.0,
+            s2: 0.0,
+        }
+    }
+
+    // The tone's amplitude, on the same scale as the samples, once a block
+    pub fn push(&mut self, sample: i16) -> Option<f32> {
+        let s = sample as f32 + self.coeff * self.s1 - self.s2;
+        self.s2 = self.s1;
+        self.s1 = s;
+        self.count += 1;
+        if self.count < self.block {
+            return None;
+        }
+        let power = self.s1 * self.s1 + self.s2 * self.s2 - self.coeff * self.s1 * self.s2;
+        self.count = 0;
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+        Some(2.0 * sqrt(power) / self.block as f32)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ToneConfig {
+    pub tone_hz: u32,
+    // Shorter blocks follow the keying more closely, longer ones let less
+    // through from either side of the tone
+    pub block_ms: Time,
+}
+
+// Turns audio into the samples the rest of the pipeline expects, one per
+// block, with the tone's amplitude standing in for the light intensity. A
+// last block cut short by the end of the audio is dropped.
+pub struct ToneEnvelope<I> {
+    samples: I,
+    goertzel: Goertzel,
+    sample_rate: u32,
+    taken: Time,
+}
+
+impl<I: Iterator<Item = i16>> ToneEnvelope<I> {
+    pub fn new(samples: I, sample_rate: u32, config: ToneConfig) -> ToneEnvelope<I> {
+        let block = (sample_rate as Time * config.block_ms / 1000).max(1) as u32;
+        ToneEnvelope {
+            samples,
+            goertzel: Goertzel::new(config.tone_hz, sample_rate, block),
+            sample_rate: sample_rate.max(1),
+            taken: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = i16>> Iterator for ToneEnvelope<I> {
+    type Item = SampledLightIntensity;
+
+    fn next(&mut self) -> Option<SampledLightIntensity> {
+        let start = self.taken;
+        loop {
+            let sample = self.samples.next()?;
+            self.taken += 1;
+            if let Some(amplitude) = self.goertzel.push(sample) {
+                return Some(SampledLightIntensity {
+                    sample_time: start * 1000 / self.sample_rate as Time,
+                    intensity: amplitude.min(LightIntensity::MAX as f32) as LightIntensity,
+                });
+            }
+        }
+    }
+}
+
+// Long enough to tell apart tones a few steps apart
+const DETECT_BLOCK_MS: Time = 50;
+
+// The frequency from min_hz to max_hz, in steps of step_hz, with the most of
+// the audio's energy. None if it is all silence.
+pub fn detect_tone<I>(
+    samples: I,
+    sample_rate: u32,
+    min_hz: u32,
+    max_hz: u32,
+    step_hz: u32,
+) -> Option<u32>
+where
+    I: Iterator<Item = i16> + Clone,
+{
+    let config = |tone_hz| ToneConfig {
+        tone_hz,
+        block_ms: DETECT_BLOCK_MS,
+    };
+    (min_hz..=max_hz)
+        .step_by(step_hz.max(1) as usize)
+        .map(|hz| {
+            let envelope = ToneEnvelope::new(samples.clone(), sample_rate, config(hz));
+            (envelope.map(|s| s.intensity as u64).sum::<u64>(), hz)
+        })
+        .filter(|(energy, _)| *energy > 0)
+        .max()
+        .map(|(_, hz)| hz)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use heapless::Vec;
+
+    const RATE: u32 = 8000;
+
+    fn helper_tone(hz: f32, amplitude: f32, n: usize) -> impl Iterator<Item = i16> + Clone {
+        (0..n).map(move |i| {
+            let phase = 2.0 * PI * hz * i as f32 / RATE as f32;
+            (amplitude * std::primitive::f32::sin(phase)) as i16
+        })
+    }
+
+    #[test]
+    fn test_helpers() {
+        for x in [0.0, 0.3, PI / 2.0, 2.5, PI].iter() {
+            assert!((cos_half_turn(*x) - std::primitive::f32::cos(*x)).abs() < 1e-5);
+        }
+        for x in [0.0, 1.0, PI, 4.0, 2.0 * PI - 1e-3].iter() {
+            assert!((sin_turn(*x) - std::primitive::f32::sin(*x)).abs() < 1e-5);
+        }
+        for x in [0.0, 1e-3, 2.0, 1e9].iter() {
+            let exact = std::primitive::f32::sqrt(*x);
+            assert!((sqrt(*x) - exact).abs() <= exact * 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_goertzel() {
+        let config = ToneConfig {
+            tone_hz: 600,
+            block_ms: 10,
+        };
+        let on: Vec<_, 8> =
+            ToneEnvelope::new(helper_tone(600.0, 10000.0, 400), RATE, config).collect();
+        assert_eq!(5, on.len());
+        assert_eq!(40, on[4].sample_time);
+        assert!(on.iter().all(|s| (9800..10200).contains(&s.intensity)));
+
+        let off: Vec<_, 8> =
+            ToneEnvelope::new(helper_tone(1200.0, 10000.0, 400), RATE, config).collect();
+        assert!(off.iter().all(|s| s.intensity < 500));
+    }
+
+    #[test]
+    fn test_detect_tone() {
+        let silence = [0; 1000].iter().copied();
+        let tone = silence.clone().chain(helper_tone(730.0, 3000.0, 4000));
+        assert_eq!(Some(730), detect_tone(tone, RATE, 400, 1000, 10));
+        assert_eq!(None, detect_tone(silence, RATE, 400, 1000, 10));
+    }
+
+    #[test]
+    fn test_tone_decode() {
+        let timing = MorseTiming::from_unit(60);
+        let mut audio: std::vec::Vec<i16> = std::vec![0; 800];
+        for pair in MorseEncoder::new("paris ", construct_key()).timed_with(timing) {
+            let (_, tle) = pair.unwrap();
+            let n = (tle.duration * RATE as Time / 1000) as usize;
+            match tle.light_state {
+                LightState::Light => audio.extend(helper_tone(700.0, 8000.0, n)),
+                LightState::Dark => audio.resize(audio.len() + n, 0),
+            }
+        }
+        let tone_hz = detect_tone(audio.iter().copied(), RATE, 300, 1200, 10).unwrap();
+
+        let mut manager: MorseManager<512, 64, 64, 256> = MorseManager::new(
+            4000,
+            MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
+                guess_after_this_many_tles: 7,
+                max_guess_ms: 120,
+                min_guess_ms: 30,
+            }),
+        );
+        let config = ToneConfig {
+            tone_hz,
+            block_ms: 5,
+        };
+        let mut chars: Vec<char, 16> = Vec::new();
+        for sample in ToneEnvelope::new(audio.iter().copied(), RATE, config) {
+            manager.add_sample(sample).unwrap();
+            let produced: Vec<char, 16> = manager.produce_chars().unwrap();
+            chars.extend(produced);
+        }
+        manager.flush().unwrap();
+        let produced: Vec<char, 16> = manager.produce_chars().unwrap();
+        chars.extend(produced);
+
+        let expected: Vec<char, 16> = "paris ".chars().collect();
+        assert_eq!(expected, chars);
+        // Within a block, as the blocks blur the edges of the marks
+        let unit_ms = manager.unit_time_estimate().unwrap();
+        assert!((55..=65).contains(&unit_ms));
+    }
+}
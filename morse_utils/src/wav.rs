@@ -0,0 +1,224 @@
+use crate::*;
+
+// A PCM WAV file, borrowed straight from its bytes. Only 8 and 16 bit
+// integer samples are read, which covers what sound cards and SDRs record.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Wav<'a> {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits: u16,
+    data: &'a [u8],
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum WavErr {
+    NotRiff,
+    NotWave,
+    Truncated,
+    NoFormat,
+    NoData,
+    // Only 1, integer PCM
+    UnsupportedFormat(u16),
+    UnsupportedBits(u16),
+    // No channels, or a sample rate of 0
+    EmptyFormat,
+}
+
+const PCM_FORMAT: u16 = 1;
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    let b = bytes.get(at..at + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// The header for a mono, 16 bit PCM file with this many samples following
+// it, each written with i16::to_le_bytes
+pub fn wav_header(sample_rate: u32, frames: u32) -> [u8; 44] {
+    let data_len = frames.saturating_mul(2);
+    let fields: [&[u8]; 12] = [
+        b"RIFF",
+        &data_len.saturating_add(36).to_le_bytes(),
+        b"WAVEfmt ",
+        &16u32.to_le_bytes(),
+        &PCM_FORMAT.to_le_bytes(),
+        &1u16.to_le_bytes(),
+        &sample_rate.to_le_bytes(),
+        &sample_rate.saturating_mul(2).to_le_bytes(),
+        &2u16.to_le_bytes(),
+        &16u16.to_le_bytes(),
+        b"data",
+        &data_len.to_le_bytes(),
+    ];
+    let mut header = [0; 44];
+    let mut at = 0;
+    for field in fields.iter() {
+        header[at..at + field.len()].copy_from_slice(field);
+        at += field.len();
+    }
+    header
+}
+
+impl<'a> Wav<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Wav<'a>, WavErr> {
+        use WavErr::*;
+        if bytes.get(0..4) != Some(b"RIFF") {
+            return Err(NotRiff);
+        }
+        if bytes.get(8..12) != Some(b"WAVE") {
+            return Err(NotWave);
+        }
+
+        let mut format = None;
+        let mut at = 12;
+        while at < bytes.len() {
+            let id = bytes.get(at..at + 4).ok_or(Truncated)?;
+            let len = u32_at(bytes, at + 4).ok_or(Truncated)? as usize;
+            let body = at + 8;
+            match id {
+                b"fmt " => {
+                    let tag = u16_at(bytes, body).ok_or(Truncated)?;
+                    let channels = u16_at(bytes, body + 2).ok_or(Truncated)?;
+                    let sample_rate = u32_at(bytes, body + 4).ok_or(Truncated)?;
+                    let bits = u16_at(bytes, body + 14).ok_or(Truncated)?;
+                    format = Some((tag, channels, sample_rate, bits));
+                }
+                b"data" => {
+                    let (tag, channels, sample_rate, bits) = format.ok_or(NoFormat)?;
+                    if tag != PCM_FORMAT {
+                        return Err(UnsupportedFormat(tag));
+                    }
+                    if bits != 8 && bits != 16 {
+                        return Err(UnsupportedBits(bits));
+                    }
+                    if channels == 0 || sample_rate == 0 {
+                        return Err(EmptyFormat);
+                    }
+                    // Recorders that were cut off leave the length too long
+                    let end = body.saturating_add(len).min(bytes.len());
+                    return Ok(Wav {
+                        sample_rate,
+                        channels,
+                        bits,
+                        data: &bytes[body..end],
+                    });
+                }
+                _ => (),
+            }
+            // Chunks are padded out to an even length
+            at = body.saturating_add(len).saturating_add(len & 1);
+        }
+        Err(NoData)
+    }
+
+    pub fn frames(&self) -> usize {
+        self.data.len() / (self.channels as usize * self.bits as usize / 8)
+    }
+
+    pub fn duration_ms(&self) -> Time {
+        self.frames() as Time * 1000 / self.sample_rate as Time
+    }
+
+    // One sample per frame, with the channels mixed down and 8 bit samples
+    // scaled up to 16 bits
+    pub fn samples(&self) -> WavSamples<'a> {
+        let frame_bytes = self.channels as usize * self.bits as usize / 8;
+        WavSamples {
+            frames: self.data.chunks_exact(frame_bytes),
+            bits: self.bits,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WavSamples<'a> {
+    frames: core::slice::ChunksExact<'a, u8>,
+    bits: u16,
+}
+
+impl<'a> Iterator for WavSamples<'a> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let frame = self.frames.next()?;
+        let sum: i32 = match self.bits {
+            8 => frame.iter().map(|b| (*b as i32 - 128) << 8).sum(),
+            _ => frame
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as i32)
+                .sum(),
+        };
+        Some((sum / (frame.len() as i32 * 8 / self.bits as i32)) as i16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::Vec;
+
+    // A canonical 44 byte header, then the samples
+    fn helper_wav<const N: usize>(
+        sample_rate: u32,
+        channels: u16,
+        bits: u16,
+        data: &[u8],
+    ) -> Vec<u8, N> {
+        let mut wav = Vec::new();
+        let byte_rate = sample_rate * channels as u32 * bits as u32 / 8;
+        let fields: [&[u8]; 13] = [
+            b"RIFF",
+            &(36 + data.len() as u32).to_le_bytes(),
+            b"WAVEfmt ",
+            &16u32.to_le_bytes(),
+            &PCM_FORMAT.to_le_bytes(),
+            &channels.to_le_bytes(),
+            &sample_rate.to_le_bytes(),
+            &byte_rate.to_le_bytes(),
+            &(channels * bits / 8).to_le_bytes(),
+            &bits.to_le_bytes(),
+            b"data",
+            &(data.len() as u32).to_le_bytes(),
+            data,
+        ];
+        for field in fields.iter() {
+            wav.extend_from_slice(field).unwrap();
+        }
+        wav
+    }
+
+    #[test]
+    fn test_parse_wav() {
+        let data = [0x00, 0x10, 0x00, 0x30, 0xff, 0xff, 0x01, 0x00];
+        let bytes: Vec<u8, 64> = helper_wav(8000, 2, 16, &data);
+        let wav = Wav::parse(&bytes).unwrap();
+        assert_eq!((8000, 2, 16), (wav.sample_rate, wav.channels, wav.bits));
+        assert_eq!(2, wav.frames());
+        let samples: Vec<i16, 4> = wav.samples().collect();
+        assert_eq!(&[0x2000, 0], &samples[..]);
+
+        let mut bytes: Vec<u8, 64> = Vec::from_slice(&wav_header(8000, 2)).unwrap();
+        bytes.extend_from_slice(&data[..4]).unwrap();
+        assert_eq!(bytes, helper_wav::<64>(8000, 1, 16, &data[..4]));
+
+        let bytes: Vec<u8, 64> = helper_wav(8000, 1, 8, &[0, 128, 255]);
+        let samples: Vec<i16, 4> = Wav::parse(&bytes).unwrap().samples().collect();
+        assert_eq!(&[-32768, 0, 32512], &samples[..]);
+    }
+
+    #[test]
+    fn test_wav_errors() {
+        assert_eq!(Err(WavErr::NotRiff), Wav::parse(b"RIFX"));
+        let mut bytes: Vec<u8, 64> = helper_wav(8000, 1, 24, &[0; 6]);
+        assert_eq!(Err(WavErr::UnsupportedBits(24)), Wav::parse(&bytes));
+        bytes[20] = 3;
+        assert_eq!(Err(WavErr::UnsupportedFormat(3)), Wav::parse(&bytes));
+        // Cut off in the middle of the fmt chunk
+        assert_eq!(Err(WavErr::Truncated), Wav::parse(&bytes[..30]));
+        assert_eq!(Err(WavErr::NoData), Wav::parse(&bytes[..36]));
+    }
+}
@@ -21,6 +21,7 @@ pub enum LcdCommand {
     ReturnHome,
     ReturnHomeAlt,
     ClearDisplay,
+    CursorLeft,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -62,6 +63,7 @@ impl InternalLcdCommand {
             PublicCommand(ReturnHome) => 0b00000010,
             PublicCommand(ClearDisplay) => 0b0000001,
             PublicCommand(ReturnHomeAlt) => 0b00000011,
+            PublicCommand(CursorLeft) => 0b00010000,
             PrivateCommand(SetDefault8Bit) => 0b00111000,
             PrivateCommand(DisplayOnCursorBlink) => 0b00001111,
             PrivateCommand(EntryModeDefault) => 0b00000110,
@@ -7,6 +7,13 @@ use lcd::{Delay, LcdObject};
 
 mod lcd;
 
+// The warm up is replayed into the converter a sample queue at a time, and
+// the loop below produces after every sample, so only a few samples are ever
+// waiting. Fewer events and elements are too.
+type Manager = morse_utils::MorseManager<'static, 16, 64, 32, 90>;
+// Lives on the stack, so leave plenty of the 40K of SRAM over
+const _: () = assert!(Manager::RAM_BYTES < 12 * 1024);
+
 #[inline(never)]
 fn delayus(tim6: &tim6::RegisterBlock, us: u16) {
 
@@ -38,8 +45,8 @@ fn setup_input(rcc: &aux9::rcc::RegisterBlock, gpioa: &aux9::gpioa::RegisterBloc
     }
 }
 
-fn ima_key(leds: &mut Leds) -> morse_utils::MorseKey {
-    morse_utils::construct_key().unwrap()
+fn ima_key(leds: &mut Leds) -> &'static morse_utils::MorseKey {
+    morse_utils::construct_key()
 }
 
 fn test_do_it(
@@ -47,7 +54,6 @@ fn test_do_it(
     gpioa: &'static gpioa::RegisterBlock,
     tim6: &'static tim6::RegisterBlock,
 ) -> morse_utils::MorseErr {
-    use heapless::consts::*;
     use heapless::spsc::*;
     use heapless::Vec;
     use morse_utils::Morse::*;
@@ -55,8 +61,8 @@ fn test_do_it(
 
     lcd.send_command(lcd::LcdCommand::ClearDisplay);
 
-    let mut chars_so_far: Vec<char, U32> = Vec::new();
-    let mut mm: MorseManager<U120, U90> = MorseManager::new(
+    let mut chars_so_far: Vec<char, 32> = Vec::new();
+    let mut mm: Manager = MorseManager::new(
         400,
         MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
             guess_after_this_many_tles: 6,
@@ -64,6 +70,12 @@ fn test_do_it(
             min_guess_ms: 100,
         }),
     );
+    // Show a '?' for a bad letter rather than stopping
+    mm.set_replacement(Some('?'));
+    // Longer than a word gap even at the slowest unit time
+    mm.set_idle_timeout(Some(10_000));
+    // Let the sender take back a word with the error signal
+    mm.set_erase_on_error_signal(true);
 
     let mut time: Time = 0;
     let mut err = None;
@@ -87,7 +99,7 @@ fn test_do_it(
             Ok(_) => (),
             Err(me) => err = Some(me),
         }
-        let new_chars: Vec<char, U8> = match mm.produce_chars() {
+        let new_chars: Vec<char, 8> = match mm.produce_chars() {
             Ok(vec) => vec,
             Err(me) => {
                 err = Some(me);
@@ -95,6 +107,16 @@ fn test_do_it(
             }
         };
         for c in new_chars.iter() {
+            if *c == ERASE {
+                // Back over each char it takes back and blank it out
+                for _ in 0..mm.last_erase_len() {
+                    chars_so_far.pop();
+                    lcd.send_command(lcd::LcdCommand::CursorLeft);
+                    lcd.send_char(' ');
+                    lcd.send_command(lcd::LcdCommand::CursorLeft);
+                }
+                continue;
+            }
             chars_so_far.push(*c);
             lcd.send_char(*c);
         }
@@ -120,7 +142,6 @@ arrr[i] = *c;
 }
 
 fn test_manager() -> bool {
-    use heapless::consts::*;
     use heapless::spsc::*;
     use heapless::Vec;
     use morse_utils::Morse::*;
@@ -168,7 +189,7 @@ fn test_manager() -> bool {
         (100, 1640),
     ];
 
-    let mut converter: MorseManager<U64, U64> = MorseManager::new(
+    let mut converter: MorseManager<128, 64, 64, 64> = MorseManager::new(
         500,
         MorseUnitTimeDecision::EstimateToBeDetermined(DeriveUnitTimeConfig {
             guess_after_this_many_tles: 7,
@@ -187,7 +208,7 @@ fn test_manager() -> bool {
         }
     }
 
-    let vec: Vec<_, U32> = match converter.produce_chars() {
+    let vec: Vec<_, 32> = match converter.produce_chars() {
         Ok(v) => v,
         _ => return false,
     };